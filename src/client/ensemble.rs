@@ -0,0 +1,52 @@
+use tokio::sync::mpsc;
+
+use super::Client;
+use crate::error::Error;
+use crate::quorum::QuorumConfig;
+use crate::session::EventType;
+
+/// Watches ensemble config and sends client addresses of servers to session on changes.
+pub(super) async fn track_servers(client: Client, sender: mpsc::UnboundedSender<Vec<(String, u16)>>) {
+    let mut state_watcher = client.state_watcher();
+    let mut last_hosts = Vec::new();
+    loop {
+        let (data, _, watcher) = match client.get_and_watch_config().await {
+            Err(Error::ConnectionLoss) => {
+                if state_watcher.wait_connected().await.is_err() {
+                    return;
+                }
+                continue;
+            },
+            Err(err) => {
+                log::debug!("ZooKeeper session {} stops tracking servers due to {}", client.session_id(), err);
+                return;
+            },
+            Ok(result) => result,
+        };
+        let mut hosts: Vec<_> = match QuorumConfig::from_bytes(&data) {
+            Err(err) => {
+                log::warn!("ZooKeeper session {} fails to parse ensemble config due to {}", client.session_id(), err);
+                Vec::new()
+            },
+            Ok(config) => config
+                .servers
+                .iter()
+                .filter_map(|server| server.connect_address())
+                .map(|(host, port)| (host.to_string(), port))
+                .collect(),
+        };
+        hosts.sort();
+        hosts.dedup();
+        if !hosts.is_empty() && hosts != last_hosts {
+            log::debug!("ZooKeeper session {} got servers {:?} from ensemble config", client.session_id(), hosts);
+            if sender.send(hosts.clone()).is_err() {
+                return;
+            }
+            last_hosts = hosts;
+        }
+        // Watches are dropped with expired session, so wait for recreated one.
+        if watcher.changed().await.event_type == EventType::Session && state_watcher.wait_connected().await.is_err() {
+            return;
+        }
+    }
+}
@@ -1,9 +1,12 @@
+mod ensemble;
+mod reinit;
 mod watcher;
 
 use std::borrow::Cow;
 use std::fmt::Write as _;
 use std::future::Future;
 use std::mem::ManuallyDrop;
+use std::sync::Arc;
 use std::time::Duration;
 
 use const_format::formatcp;
@@ -11,12 +14,27 @@ use either::{Either, Left, Right};
 use ignore_result::Ignore;
 use thiserror::Error;
 use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
 
+use self::reinit::ReinitHook;
 pub use self::watcher::{OneshotWatcher, PersistentWatcher, StateWatcher};
-use super::session::{Depot, MarshalledRequest, Session, SessionOperation, WatchReceiver, PASSWORD_LEN};
+use super::session::{
+    Connector,
+    Depot,
+    MarshalledRequest,
+    RequestLimiter,
+    SaslOptions,
+    Session,
+    SessionInfo,
+    SessionOperation,
+    WatchReceiver,
+    PASSWORD_LEN,
+};
 use crate::acl::{Acl, Acls, AuthUser};
 use crate::chroot::{Chroot, ChrootPath, OwnedChroot};
 use crate::error::Error;
+use crate::host::{HostProvider, HostProviderFactory};
+pub use crate::proto::Stat;
 use crate::proto::{
     self,
     AuthPacket,
@@ -40,10 +58,12 @@ use crate::proto::{
     SetDataRequest,
     SyncRequest,
 };
-pub use crate::proto::{EnsembleUpdate, Stat};
+use crate::quorum::EnsembleUpdate;
 use crate::record::{self, Record, StaticRecord};
 use crate::session::StateReceiver;
-pub use crate::session::{EventType, SessionId, SessionState, WatchedEvent};
+pub use crate::session::{EventType, ReconnectBackoff, SessionId, SessionState, WatchedEvent};
+#[cfg(feature = "tls")]
+use crate::tls::TlsOptions;
 use crate::util::{self, Ref as _};
 
 type Result<T> = std::result::Result<T, Error>;
@@ -65,11 +85,11 @@ impl CreateMode {
         CreateOptions { mode: self, acls, ttl: None }
     }
 
-    fn is_sequential(self) -> bool {
+    pub(crate) fn is_sequential(self) -> bool {
         self == CreateMode::PersistentSequential || self == CreateMode::EphemeralSequential
     }
 
-    fn is_ephemeral(self) -> bool {
+    pub(crate) fn is_ephemeral(self) -> bool {
         self == Self::Ephemeral || self == Self::EphemeralSequential
     }
 
@@ -123,9 +143,9 @@ impl From<AddWatchMode> for proto::AddWatchMode {
 /// Options for node creation, constructed from [CreateMode::with_acls].
 #[derive(Clone, Debug)]
 pub struct CreateOptions<'a> {
-    mode: CreateMode,
-    acls: Acls<'a>,
-    ttl: Option<Duration>,
+    pub(crate) mode: CreateMode,
+    pub(crate) acls: Acls<'a>,
+    pub(crate) ttl: Option<Duration>,
 }
 
 // Five bytes are avaiable for milliseconds. See javadoc of EphemeralType in ZooKeeper for reference.
@@ -140,7 +160,7 @@ impl<'a> CreateOptions<'a> {
         self
     }
 
-    fn validate(&'a self) -> Result<()> {
+    pub(crate) fn validate(&'a self) -> Result<()> {
         if let Some(ref ttl) = self.ttl {
             if self.mode != CreateMode::Persistent && self.mode != CreateMode::PersistentSequential {
                 return Err(Error::BadArguments(&"ttl can only be specified with persistent node"));
@@ -174,19 +194,25 @@ impl std::fmt::Display for CreateSequence {
 /// Besides semantic errors, node operations could also fail due to cluster availability and
 /// limitations, e.g. [Error::ConnectionLoss], [Error::QuotaExceeded] and so on.
 ///
-/// All remote operations will fail after session expired, failed or closed.
+/// All remote operations will fail after session expired, failed or closed. See
+/// [ClientBuilder::with_session_recreation] to create new session after expired.
 ///
 /// # Notable behaviors
 /// * All cloned clients share same authentication identities.
 /// * All methods construct resulting future by sending request synchronously and polling output
 ///   asynchronously. This guarantees that requests are sending to server in the order of method
 ///   call but not future evaluation.
+/// * Exception to above, request exceeding limits of [ClientBuilder::with_max_outstanding_requests]
+///   or [ClientBuilder::with_max_outstanding_bytes] is sent in evaluation of its future after
+///   outstanding requests completed, so it could be reordered with requests from later calls.
 #[derive(Clone, Debug)]
 pub struct Client {
     chroot: OwnedChroot,
-    session: (SessionId, Vec<u8>),
-    session_timeout: Duration,
+    session: watch::Receiver<SessionInfo>,
+    request_timeout: Duration,
     requester: mpsc::UnboundedSender<SessionOperation>,
+    limiter: Arc<RequestLimiter>,
+    fail_fast: bool,
     state_watcher: StateWatcher,
 }
 
@@ -205,13 +231,20 @@ impl Client {
 
     pub(crate) fn new(
         chroot: OwnedChroot,
-        session: (SessionId, Vec<u8>),
-        timeout: Duration,
+        session: watch::Receiver<SessionInfo>,
         requester: mpsc::UnboundedSender<SessionOperation>,
-        state_receiver: watch::Receiver<SessionState>,
+        state_receiver: watch::Receiver<(SessionState, u32)>,
     ) -> Client {
         let state_watcher = StateWatcher::new(state_receiver);
-        Client { chroot, session, session_timeout: timeout, requester, state_watcher }
+        Client {
+            chroot,
+            session,
+            request_timeout: Duration::ZERO,
+            requester,
+            limiter: Default::default(),
+            fail_fast: false,
+            state_watcher,
+        }
     }
 
     fn validate_path<'a>(&'a self, path: &'a str) -> Result<ChrootPath<'a>> {
@@ -228,23 +261,33 @@ impl Client {
     }
 
     /// ZooKeeper session id.
+    ///
+    /// It changes after session recreated, see [ClientBuilder::with_session_recreation].
     pub fn session_id(&self) -> SessionId {
-        self.session.0
+        self.session.borrow().id
     }
 
     /// Session password.
-    pub fn session_password(&self) -> &[u8] {
-        self.session.1.as_slice()
+    ///
+    /// It changes after session recreated, see [ClientBuilder::with_session_recreation].
+    pub fn session_password(&self) -> Vec<u8> {
+        self.session.borrow().password.clone()
     }
 
     /// Consumes this instance into session info.
     pub fn into_session(self) -> (SessionId, Vec<u8>) {
-        self.session
+        let session = self.session.borrow();
+        (session.id, session.password.clone())
     }
 
     /// Negotiated session timeout.
     pub fn session_timeout(&self) -> Duration {
-        self.session_timeout
+        self.session.borrow().timeout
+    }
+
+    /// Number of outstanding requests, that is, requests sent but not yet responded.
+    pub fn outstanding_requests(&self) -> usize {
+        self.limiter.outstanding()
     }
 
     /// Latest session state.
@@ -254,9 +297,27 @@ impl Client {
 
     /// Creates a [StateWatcher] to track future session state updates.
     pub fn state_watcher(&self) -> StateWatcher {
-        let mut watcher = self.state_watcher.clone();
-        watcher.state();
-        watcher
+        self.state_watcher.renew()
+    }
+
+    /// Request timeout for operations, zero means no timeout.
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    /// Clones a client with given request timeout for operations, zero means no timeout.
+    ///
+    /// Operations fail with [Error::Timeout] if no response received in time. Requests not yet
+    /// sent are dropped, while sent requests could still take effect in server.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # async fn example(client: &zookeeper_client::Client) {
+    /// let (data, stat) = client.with_timeout(Duration::from_secs(1)).get_data("/app").await.unwrap();
+    /// # }
+    /// ```
+    pub fn with_timeout(&self, timeout: Duration) -> Client {
+        Client { request_timeout: timeout, ..self.clone() }
     }
 
     /// Changes root directory to given absolute path.
@@ -280,14 +341,41 @@ impl Client {
     }
 
     fn send_marshalled_request(&self, request: MarshalledRequest) -> StateReceiver {
-        let (operation, receiver) = SessionOperation::new_marshalled(request).with_responser();
-        if let Err(mpsc::error::SendError(operation)) = self.requester.send(operation) {
-            let state = self.state();
-            operation.responser.send(Err(state.to_error()));
+        let deadline = if self.request_timeout.is_zero() { None } else { Some(Instant::now() + self.request_timeout) };
+        let len = request.0.len();
+        let (mut operation, receiver) =
+            SessionOperation::new_marshalled(request).with_deadline(deadline).with_responser();
+        match self.limiter.try_acquire(len) {
+            Ok(permit) => operation.responser.set_permit(permit),
+            Err(err) if self.fail_fast => {
+                operation.responser.send(Err(err));
+                return receiver;
+            },
+            Err(_) => {
+                let limiter = self.limiter.clone();
+                let requester = self.requester.clone();
+                let state_watcher = self.state_watcher.clone();
+                return receiver.with_sending(async move {
+                    operation.responser.set_permit(limiter.acquire(len).await);
+                    Self::send_operation(&requester, &state_watcher, operation);
+                });
+            },
         }
+        Self::send_operation(&self.requester, &self.state_watcher, operation);
         receiver
     }
 
+    fn send_operation(
+        requester: &mpsc::UnboundedSender<SessionOperation>,
+        state_watcher: &StateWatcher,
+        operation: SessionOperation,
+    ) {
+        if let Err(mpsc::error::SendError(operation)) = requester.send(operation) {
+            let state = state_watcher.peek_state();
+            operation.responser.send(Err(state.to_error()));
+        }
+    }
+
     async fn wait<T, E, F>(result: std::result::Result<F, E>) -> std::result::Result<T, E>
     where
         F: Future<Output = std::result::Result<T, E>>, {
@@ -418,7 +506,9 @@ impl Client {
     }
 
     // TODO: move these to session side so to eliminate owned Client and String.
-    fn delete_background(self, path: String) {
+    pub(crate) fn delete_background(mut self, path: String) {
+        // Waits for outstanding limits other than failing fast, otherwise node could leak.
+        self.fail_fast = false;
         tokio::spawn(async move {
             self.delete_foreground(&path).await;
         });
@@ -428,12 +518,14 @@ impl Client {
         Client::retry_on_connection_loss(|| self.delete(path, None)).await.ignore();
     }
 
-    fn delete_ephemeral_background(self, prefix: String, unique: bool) {
+    pub(crate) fn delete_ephemeral_background(mut self, prefix: String, unique: bool) {
+        self.fail_fast = false;
         tokio::spawn(async move {
             let (parent, tree, name) = util::split_path(&prefix);
             let mut children = Self::retry_on_connection_loss(|| self.list_children(parent)).await?;
             if unique {
                 if let Some(i) = children.iter().position(|s| s.starts_with(name)) {
+                    children[i].insert_str(0, tree);
                     self.delete_foreground(&children[i]).await;
                 };
                 return Ok::<(), Error>(());
@@ -874,12 +966,18 @@ impl Client {
     ///
     /// # References
     /// See [ZooKeeper Dynamic Reconfiguration](https://zookeeper.apache.org/doc/current/zookeeperReconfig.html).
-    pub fn update_ensemble<'a, I: Iterator<Item = &'a str> + Clone>(
+    pub fn update_ensemble(
         &self,
-        update: EnsembleUpdate<'a, I>,
+        update: EnsembleUpdate<'_>,
         expected_zxid: Option<i64>,
     ) -> impl Future<Output = Result<(Vec<u8>, Stat)>> + Send {
-        let request = ReconfigRequest { update, version: expected_zxid.unwrap_or(-1) };
+        let (joining_servers, leaving_servers, new_members) = update.to_server_lists();
+        let request = ReconfigRequest {
+            joining_servers: &joining_servers,
+            leaving_servers: &leaving_servers,
+            new_members: &new_members,
+            version: expected_zxid.unwrap_or(-1),
+        };
         let receiver = self.send_request(OpCode::Reconfig, &request);
         async move {
             let (mut body, _) = receiver.await?;
@@ -911,7 +1009,7 @@ impl Client {
         Ok(CheckWriter { writer })
     }
 
-    async fn create_ancestor_backword(&self, path: &str, options: &CreateOptions<'_>) -> Result<()> {
+    pub(crate) async fn create_ancestor_backword(&self, path: &str, options: &CreateOptions<'_>) -> Result<()> {
         let mut j = path.len();
         loop {
             match Self::retry_on_connection_loss(|| self.create(&path[..j], Default::default(), options)).await {
@@ -942,7 +1040,7 @@ impl Client {
         prefix: LockPrefix<'_>,
         data: &[u8],
         options: LockOptions<'_>,
-    ) -> Result<(String, usize)> {
+    ) -> Result<(String, usize, Option<i64>)> {
         let kind = prefix.kind();
         let prefix = prefix.into();
         self.validate_sequential_path(&prefix)?;
@@ -959,10 +1057,10 @@ impl Client {
                     }
                 }
             }
-            let sequence = match result {
+            let (sequence, czxid) = match result {
                 Err(Error::ConnectionLoss) => {
                     if let Some(sequence) = self.find_lock(&prefix, kind).await? {
-                        sequence
+                        (sequence, None)
                     } else {
                         continue;
                     }
@@ -975,14 +1073,14 @@ impl Client {
                         return Err(err);
                     }
                 },
-                Ok((_stat, sequence)) => sequence,
+                Ok((stat, sequence)) => (sequence, Some(stat.czxid)),
             };
             std::mem::forget(guard);
             let prefix_len = prefix.len();
             let mut path = prefix;
             write!(&mut path, "{}", sequence).unwrap();
             let sequence_len = path.len() - prefix_len;
-            return Ok((path, sequence_len));
+            return Ok((path, sequence_len, czxid));
         }
     }
 
@@ -1024,7 +1122,14 @@ impl Client {
         Ok(None)
     }
 
-    async fn wait_lock(&self, lock: &str, kind: LockPrefixKind<'_>, sequence_len: usize) -> Result<()> {
+    /// Waits until lock acquired or deadline elapsed, returns false in later case.
+    async fn wait_lock(
+        &self,
+        lock: &str,
+        kind: LockPrefixKind<'_>,
+        sequence_len: usize,
+        deadline: Option<Instant>,
+    ) -> Result<bool> {
         let (parent, tree, this) = util::split_path(lock);
         loop {
             let mut children = Self::retry_on_connection_loss(|| self.list_children(parent)).await?;
@@ -1033,16 +1138,30 @@ impl Client {
             });
             children.sort_unstable_by(|a, b| a[a.len() - sequence_len..].cmp(&b[b.len() - sequence_len..]));
             match children.binary_search_by(|a| a[a.len() - sequence_len..].cmp(&this[this.len() - sequence_len..])) {
-                Ok(0) => return Ok(()),
                 Ok(i) => {
-                    let mut child = children.swap_remove(i - 1);
+                    let Some(j) = children[..i].iter().rposition(|s| kind.is_blocked_by(s)) else {
+                        return Ok(true);
+                    };
+                    if deadline.map(|deadline| deadline <= Instant::now()).unwrap_or(false) {
+                        return Ok(false);
+                    }
+                    let mut child = children.swap_remove(j);
                     child.insert_str(0, tree);
                     let watcher = match Self::retry_on_connection_loss(|| self.get_and_watch_data(&child)).await {
                         Err(Error::NoNode) => continue,
                         Err(err) => return Err(err),
                         Ok((_data, _stat, watcher)) => watcher,
                     };
-                    watcher.changed().await;
+                    match deadline {
+                        None => {
+                            watcher.changed().await;
+                        },
+                        Some(deadline) => {
+                            if tokio::time::timeout_at(deadline, watcher.changed()).await.is_err() {
+                                return Ok(false);
+                            }
+                        },
+                    }
                 },
                 Err(_) => return Err(Error::RuntimeInconsistent),
             }
@@ -1060,7 +1179,8 @@ impl Client {
     ///
     /// # Cancellation safety
     /// This method is cancellation safe, so you can free to cancel result future without fear to
-    /// dangle lock. For example, a timed lock is easy to construct with `select!` and `sleep`.
+    /// dangle lock. Lock path is deleted in background after cancellation, see
+    /// [Client::try_lock] and [Client::lock_with_timeout] for deletion in foreground.
     ///
     /// # Asynchronous ordering
     /// Comparing to other data operations, e.g. [Client::create], this operation is pure
@@ -1085,20 +1205,84 @@ impl Client {
         data: &[u8],
         options: impl Into<LockOptions<'_>>,
     ) -> Result<LockClient<'_>> {
-        let options = options.into();
+        let client = self.lock_internally(prefix, data, options.into(), None).await?;
+        // It is always `Some` as there is no deadline.
+        Ok(unsafe { client.unwrap_unchecked() })
+    }
+
+    /// Similar to [Client::lock] except that it returns `Ok(None)` if lock is held by others.
+    ///
+    /// Lock path is deleted before returning `Ok(None)`, so it never blocks others.
+    ///
+    /// # Notable errors
+    /// Same as [Client::lock].
+    pub async fn try_lock(
+        &self,
+        prefix: LockPrefix<'_>,
+        data: &[u8],
+        options: impl Into<LockOptions<'_>>,
+    ) -> Result<Option<LockClient<'_>>> {
+        self.lock_internally(prefix, data, options.into(), Some(Instant::now())).await
+    }
+
+    /// Similar to [Client::lock] except that it returns `Ok(None)` if lock is not acquired in
+    /// given timeout.
+    ///
+    /// Lock path is deleted before returning `Ok(None)`, so it never blocks others. Timeout does
+    /// not apply to lock path creation, so this method could exceed it due to connection loss.
+    ///
+    /// # Notable errors
+    /// Same as [Client::lock].
+    pub async fn lock_with_timeout(
+        &self,
+        prefix: LockPrefix<'_>,
+        data: &[u8],
+        options: impl Into<LockOptions<'_>>,
+        timeout: Duration,
+    ) -> Result<Option<LockClient<'_>>> {
+        let deadline = Instant::now() + timeout;
+        self.lock_internally(prefix, data, options.into(), Some(deadline)).await
+    }
+
+    async fn lock_internally(
+        &self,
+        prefix: LockPrefix<'_>,
+        data: &[u8],
+        options: LockOptions<'_>,
+        deadline: Option<Instant>,
+    ) -> Result<Option<LockClient<'_>>> {
         if options.acls.is_empty() {
             return Err(Error::InvalidAcl);
         }
         let prefix_kind = prefix.kind();
-        let (lock, sequence_len) = self.create_lock(prefix, data, options).await?;
-        let client = LockClient { client: self, lock: Cow::from(lock) };
-        match self.wait_lock(&client.lock, prefix_kind, sequence_len).await {
+        let (lock, sequence_len, czxid) = self.create_lock(prefix, data, options).await?;
+        let mut client = LockClient { client: self, lock: Cow::from(lock), czxid: czxid.unwrap_or(-1) };
+        match self.wait_lock(&client.lock, prefix_kind, sequence_len, deadline).await {
             Err(err @ (Error::RuntimeInconsistent | Error::SessionExpired)) => {
-                std::mem::forget(client);
+                client.into_lock_path();
                 Err(err)
             },
             Err(err) => Err(err),
-            Ok(_) => Ok(client),
+            Ok(true) if czxid.is_some() => Ok(Some(client)),
+            Ok(true) => match Self::retry_on_connection_loss(|| self.check_stat(&client.lock)).await {
+                Ok(Some(stat)) => {
+                    client.czxid = stat.czxid;
+                    Ok(Some(client))
+                },
+                Ok(None) => {
+                    client.into_lock_path();
+                    Err(Error::RuntimeInconsistent)
+                },
+                Err(err) => Err(err),
+            },
+            Ok(false) => match Self::retry_on_connection_loss(|| self.delete(&client.lock, None)).await {
+                Ok(_) | Err(Error::NoNode | Error::SessionExpired) => {
+                    client.into_lock_path();
+                    Ok(None)
+                },
+                // Leave it to background deletion.
+                Err(err) => Err(err),
+            },
         }
     }
 }
@@ -1107,8 +1291,8 @@ impl Client {
 /// exist.
 #[derive(Clone, Debug)]
 pub struct LockOptions<'a> {
-    acls: Acls<'a>,
-    parent: Option<CreateOptions<'a>>,
+    pub(crate) acls: Acls<'a>,
+    pub(crate) parent: Option<CreateOptions<'a>>,
 }
 
 impl<'a> LockOptions<'a> {
@@ -1139,9 +1323,13 @@ impl<'a> From<Acls<'a>> for LockOptions<'a> {
     }
 }
 
+const CURATOR_READ_LOCK_NAME: &str = "__READ__";
+const CURATOR_WRITE_LOCK_NAME: &str = "__WRIT__";
+
 #[derive(Clone, Copy)]
 enum LockPrefixKind<'a> {
     Curator { lock_name: &'a str },
+    CuratorReadWrite { read: bool },
     Custom { lock_name: &'a str },
     Shared { prefix: &'a str },
 }
@@ -1150,18 +1338,30 @@ impl<'a> LockPrefixKind<'a> {
     fn filter(&self, name: &str) -> bool {
         match self {
             Self::Curator { lock_name } => name.contains(lock_name),
+            Self::CuratorReadWrite { .. } => {
+                name.contains(CURATOR_READ_LOCK_NAME) || name.contains(CURATOR_WRITE_LOCK_NAME)
+            },
             Self::Custom { lock_name } => name.contains(lock_name),
             Self::Shared { prefix } => name.starts_with(prefix),
         }
     }
 
+    /// Returns whether contender with given name blocks later contender of this kind.
+    fn is_blocked_by(&self, name: &str) -> bool {
+        match self {
+            Self::CuratorReadWrite { read: true } => name.contains(CURATOR_WRITE_LOCK_NAME),
+            _ => true,
+        }
+    }
+
     fn is_unique(&self) -> bool {
-        matches!(self, Self::Curator { .. })
+        matches!(self, Self::Curator { .. } | Self::CuratorReadWrite { .. })
     }
 }
 
 enum LockPrefixInner<'a> {
     Curator { dir: &'a str, name: &'a str },
+    CuratorReadWrite { dir: &'a str, read: bool },
     Custom { prefix: String, name: &'a str },
     Shared { prefix: &'a str },
 }
@@ -1185,6 +1385,9 @@ impl<'a> LockPrefix<'a> {
     /// # Notable lock names
     /// * `latch-` for `LeaderLatch`.
     /// * `lock-` for `LeaderSelector` and `InterProcessMutex`.
+    ///
+    /// See [LockPrefix::new_curator_read] and [LockPrefix::new_curator_write] for
+    /// `InterProcessReadWriteLock`.
     pub fn new_curator(dir: &'a str, name: &'a str) -> Result<Self> {
         crate::util::validate_path(Chroot::default(), dir, false)?;
         if name.find('/').is_some() {
@@ -1193,6 +1396,24 @@ impl<'a> LockPrefix<'a> {
         Ok(Self { inner: LockPrefixInner::Curator { dir, name } })
     }
 
+    /// Apache Curator compatible prefix pattern for read lock of `InterProcessReadWriteLock`, the
+    /// final lock path will be `{dir}/_c_{uuid}-__READ__{ephemeral_sequence}`.
+    ///
+    /// Read lock is shared among readers, it waits only for earlier write locks in `dir`.
+    pub fn new_curator_read(dir: &'a str) -> Result<Self> {
+        crate::util::validate_path(Chroot::default(), dir, false)?;
+        Ok(Self { inner: LockPrefixInner::CuratorReadWrite { dir, read: true } })
+    }
+
+    /// Apache Curator compatible prefix pattern for write lock of `InterProcessReadWriteLock`, the
+    /// final lock path will be `{dir}/_c_{uuid}-__WRIT__{ephemeral_sequence}`.
+    ///
+    /// Write lock is exclusive, it waits for all earlier read and write locks in `dir`.
+    pub fn new_curator_write(dir: &'a str) -> Result<Self> {
+        crate::util::validate_path(Chroot::default(), dir, false)?;
+        Ok(Self { inner: LockPrefixInner::CuratorReadWrite { dir, read: false } })
+    }
+
     /// Shared path prefix, the final lock path will be `{prefix}{ephemeral_sequence}`.
     ///
     /// # CAUTION
@@ -1238,6 +1459,7 @@ impl<'a> LockPrefix<'a> {
     fn kind(&self) -> LockPrefixKind<'a> {
         match &self.inner {
             LockPrefixInner::Curator { name, .. } => LockPrefixKind::Curator { lock_name: name },
+            LockPrefixInner::CuratorReadWrite { read, .. } => LockPrefixKind::CuratorReadWrite { read: *read },
             LockPrefixInner::Shared { prefix } => {
                 let (_parent, _tree, name) = util::split_path(prefix);
                 LockPrefixKind::Shared { prefix: name }
@@ -1249,6 +1471,10 @@ impl<'a> LockPrefix<'a> {
     fn into(self) -> String {
         match self.inner {
             LockPrefixInner::Curator { dir, name } => format!("{}/_c_{}-{}", dir, uuid::Uuid::new_v4(), name),
+            LockPrefixInner::CuratorReadWrite { dir, read } => {
+                let name = if read { CURATOR_READ_LOCK_NAME } else { CURATOR_WRITE_LOCK_NAME };
+                format!("{}/_c_{}-{}", dir, uuid::Uuid::new_v4(), name)
+            },
             LockPrefixInner::Shared { prefix } => prefix.to_string(),
             LockPrefixInner::Custom { prefix, .. } => prefix,
         }
@@ -1272,6 +1498,7 @@ impl Drop for LockingGuard<'_> {
 pub struct LockClient<'a> {
     client: &'a Client,
     lock: Cow<'a, str>,
+    czxid: i64,
 }
 
 impl<'a> LockClient<'a> {
@@ -1304,6 +1531,28 @@ impl<'a> LockClient<'a> {
         &self.lock
     }
 
+    /// Fencing token of this lock, that is the zxid in which lock path was created.
+    ///
+    /// It increases monotonically among successive lock holders, so external systems could reject
+    /// requests from stale holders by comparing it with the highest token seen.
+    pub fn fencing_token(&self) -> i64 {
+        self.czxid
+    }
+
+    /// Verifies that lock path still exists and is owned by this session.
+    ///
+    /// It is a plain read, which could be served by a lagging server, so it narrows but does not
+    /// close the window for stale holders. Use [LockClient::fencing_token] to close it.
+    ///
+    /// # Notable errors
+    /// * [Error::RuntimeInconsistent] if lock lost.
+    pub async fn check_held(&self) -> Result<()> {
+        match self.client.check_stat(&self.lock).await? {
+            Some(stat) if stat.czxid == self.czxid && stat.ephemeral_owner == self.client.session_id().0 => Ok(()),
+            _ => Err(Error::RuntimeInconsistent),
+        }
+    }
+
     /// Similar to [Client::create] except [Error::RuntimeInconsistent] if lock lost.
     ///
     /// # BUG
@@ -1396,12 +1645,18 @@ impl<'a> LockClient<'a> {
         unsafe { self.client.new_check_writer(&self.lock, None).unwrap_unchecked() }
     }
 
+    /// Takes lock path without deleting it.
+    fn into_lock_path(self) -> String {
+        let mut drop = ManuallyDrop::new(self);
+        std::mem::take(drop.lock.to_mut())
+    }
+
     /// Converts to [OwnedLockClient].
     pub fn into_owned(self) -> OwnedLockClient {
         let client = self.client.clone();
-        let mut drop = ManuallyDrop::new(self);
-        let lock = std::mem::take(drop.lock.to_mut());
-        OwnedLockClient { client: ManuallyDrop::new(client), lock }
+        let czxid = self.czxid;
+        let lock = self.into_lock_path();
+        OwnedLockClient { client: ManuallyDrop::new(client), lock, czxid }
     }
 }
 
@@ -1419,11 +1674,12 @@ impl Drop for LockClient<'_> {
 pub struct OwnedLockClient {
     client: ManuallyDrop<Client>,
     lock: String,
+    czxid: i64,
 }
 
 impl OwnedLockClient {
     fn lock_client(&self) -> std::mem::ManuallyDrop<LockClient<'_>> {
-        std::mem::ManuallyDrop::new(LockClient { client: &self.client, lock: Cow::from(&self.lock) })
+        std::mem::ManuallyDrop::new(LockClient { client: &self.client, lock: Cow::from(&self.lock), czxid: self.czxid })
     }
 
     /// Underlying client.
@@ -1436,6 +1692,16 @@ impl OwnedLockClient {
         &self.lock
     }
 
+    /// Same as [LockClient::fencing_token].
+    pub fn fencing_token(&self) -> i64 {
+        self.czxid
+    }
+
+    /// Same as [LockClient::check_held].
+    pub async fn check_held(&self) -> Result<()> {
+        self.lock_client().check_held().await
+    }
+
     /// Same as [LockClient::create].
     pub fn create<'a: 'f, 'b: 'f, 'f>(
         &'a self,
@@ -1480,6 +1746,18 @@ impl Drop for OwnedLockClient {
 #[derive(Clone, Debug)]
 pub struct ClientBuilder {
     authes: Vec<AuthPacket>,
+    sasl: Option<SaslOptions>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsOptions>,
+    host_provider: HostProviderFactory,
+    track_servers: bool,
+    reconnect_backoff: Option<ReconnectBackoff>,
+    recreate_session: bool,
+    reinit_hooks: Vec<ReinitHook>,
+    request_timeout: Duration,
+    max_outstanding_requests: usize,
+    max_outstanding_bytes: usize,
+    fail_fast: bool,
     session: Option<(SessionId, Vec<u8>)>,
     readonly: bool,
     detached: bool,
@@ -1491,6 +1769,18 @@ impl ClientBuilder {
     fn new() -> Self {
         Self {
             authes: Default::default(),
+            sasl: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            host_provider: Default::default(),
+            track_servers: false,
+            reconnect_backoff: None,
+            recreate_session: false,
+            reinit_hooks: Vec::new(),
+            request_timeout: Duration::ZERO,
+            max_outstanding_requests: 0,
+            max_outstanding_bytes: 0,
+            fail_fast: false,
             session: None,
             readonly: false,
             detached: false,
@@ -1515,7 +1805,52 @@ impl ClientBuilder {
         self
     }
 
+    /// Specifies default request timeout for operations, see [Client::with_timeout].
+    ///
+    /// Defaults to zero, that is, no timeout.
+    pub fn with_request_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Specifies maximum number of outstanding requests, zero means no limit.
+    ///
+    /// Requests exceeding limits wait for completion of outstanding ones unless
+    /// [ClientBuilder::with_fail_fast] is specified. Requests are sent in order of calls only if
+    /// they are not waiting.
+    ///
+    /// Defaults to zero.
+    pub fn with_max_outstanding_requests(&mut self, requests: usize) -> &mut Self {
+        self.max_outstanding_requests = requests;
+        self
+    }
+
+    /// Specifies maximum bytes of outstanding requests, zero means no limit.
+    ///
+    /// Request larger than this limit is sent after all outstanding requests completed.
+    ///
+    /// Defaults to zero.
+    pub fn with_max_outstanding_bytes(&mut self, bytes: usize) -> &mut Self {
+        self.max_outstanding_bytes = bytes;
+        self
+    }
+
+    /// Specifies whether to fail requests exceeding outstanding limits with
+    /// [Error::TooManyRequests] instead of waiting.
+    ///
+    /// Background requests issued by client itself, say, deletions of nodes in drop, always wait.
+    ///
+    /// Defaults to `false`.
+    pub fn with_fail_fast(&mut self, fail_fast: bool) -> &mut Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
     /// Specifies whether readonly server is allowed.
+    ///
+    /// Session connected to readonly server probes other servers in background, and reconnects
+    /// to read-write server once found. New requests are held back until in-flight ones complete
+    /// before reconnection.
     pub fn with_readonly(&mut self, readonly: bool) -> &mut ClientBuilder {
         self.readonly = readonly;
         self
@@ -1527,6 +1862,100 @@ impl ClientBuilder {
         self
     }
 
+    /// Specifies SASL DIGEST-MD5 credential to authenticate with.
+    ///
+    /// Authentication is performed in every connection before any other requests, including
+    /// [ClientBuilder::with_auth], are sent. Failure of authentication results in
+    /// [SessionState::AuthFailed].
+    pub fn with_sasl_digest(&mut self, username: String, password: String) -> &mut Self {
+        self.sasl = Some(SaslOptions::DigestMd5 { username, password });
+        self
+    }
+
+    /// Specifies tls options to connect to servers' secure client port.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(&mut self, options: TlsOptions) -> &mut Self {
+        self.tls = Some(options);
+        self
+    }
+
+    /// Specifies host provider, built from hosts in connect string, for session to connect to.
+    ///
+    /// Defaults to [crate::StaticHostProvider].
+    pub fn with_host_provider<F, P>(&mut self, factory: F) -> &mut Self
+    where
+        F: Fn(Vec<(String, u16)>) -> P + Send + Sync + 'static,
+        P: HostProvider, {
+        self.host_provider = HostProviderFactory::new(factory);
+        self
+    }
+
+    /// Specifies whether to track servers from ensemble config.
+    ///
+    /// Client addresses of servers in "/zookeeper/config" are fed to host provider after dynamic
+    /// reconfiguration, so session could connect to newly joined servers. Session could reconnect
+    /// to rebalance load among servers as `updateServerList` in Java client does, in which case
+    /// outstanding requests will fail with [Error::ConnectionLoss].
+    ///
+    /// Defaults to `false`.
+    pub fn with_server_tracking(&mut self, track: bool) -> &mut Self {
+        self.track_servers = track;
+        self
+    }
+
+    /// Specifies backoff between rounds of reconnection attempts to servers.
+    ///
+    /// Defaults to no backoff, that is, session retries servers back to back except a short
+    /// pause when there is no server to connect.
+    pub fn with_reconnect_backoff(&mut self, backoff: ReconnectBackoff) -> &mut Self {
+        self.reconnect_backoff = Some(backoff);
+        self
+    }
+
+    /// Specifies whether to create new session after session expired.
+    ///
+    /// Once session expired, outstanding requests and watchers fail with
+    /// [Error::SessionExpired], and a new session is created under all cloned clients.
+    /// [SessionState::Reestablished] is reported in place of [SessionState::Expired] after new
+    /// session established, and followed by connected state of the new session. Ephemeral nodes,
+    /// watches and sequential node locks are gone with expired session, see
+    /// [ClientBuilder::with_reinit_hook] to restore them.
+    ///
+    /// Defaults to `false`.
+    pub fn with_session_recreation(&mut self, recreate: bool) -> &mut Self {
+        self.recreate_session = recreate;
+        self
+    }
+
+    /// Registers hook to run after new session established in place of expired one.
+    ///
+    /// Hooks run in order of registration, with client of this builder, one by one in background.
+    /// The client passed to hook does not keep session alive. It implies
+    /// [ClientBuilder::with_session_recreation].
+    ///
+    /// ```no_run
+    /// # async fn example() {
+    /// use zookeeper_client as zk;
+    ///
+    /// let client = zk::Client::builder()
+    ///     .with_reinit_hook(|client: zk::Client| async move {
+    ///         let options = zk::CreateMode::Ephemeral.with_acls(zk::Acls::anyone_all());
+    ///         client.create("/app/members/node1", b"", &options).await.ok();
+    ///     })
+    ///     .connect("localhost:2181")
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn with_reinit_hook<F, Fut>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(Client) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static, {
+        self.recreate_session = true;
+        self.reinit_hooks.push(ReinitHook::new(hook));
+        self
+    }
+
     /// Specifies session to reestablish.
     pub fn with_session(&mut self, id: SessionId, password: Vec<u8>) -> &mut Self {
         self.session = Some((id, password));
@@ -1559,24 +1988,54 @@ impl ClientBuilder {
         } else if self.connection_timeout < Duration::ZERO {
             return Err(Error::BadArguments(&"connection timeout must not be negative"));
         }
+        if let Some(backoff) = self.reconnect_backoff.as_ref() {
+            backoff.validate().map_err(Error::BadArguments)?;
+        }
+        #[cfg(feature = "tls")]
+        let connector = match self.tls.clone() {
+            None => Connector::default(),
+            Some(options) => Connector::with_tls(options.into_config()?),
+        };
+        #[cfg(not(feature = "tls"))]
+        let connector = Connector::default();
+        let host_provider = self.host_provider.create(hosts.iter().map(|addr| addr.to_value()).collect());
         let (mut session, state_receiver) = Session::new(
+            connector,
+            host_provider,
             self.session.take(),
             &self.authes,
+            self.sasl.clone(),
+            self.reconnect_backoff,
             self.readonly,
             self.detached,
+            self.recreate_session,
             self.session_timeout,
             self.connection_timeout,
         );
-        let mut hosts_iter = hosts.iter().copied();
-        let sock = session.start(&mut hosts_iter, &mut buf, &mut connecting_depot).await?;
+        let sock = session.start(1, &mut buf, &mut connecting_depot).await?;
+        let tracker = if self.track_servers { Some(session.track_servers()) } else { None };
+        let background_requester = if tracker.is_some() || !self.reinit_hooks.is_empty() {
+            Some(session.background_requester())
+        } else {
+            None
+        };
         let (sender, receiver) = mpsc::unbounded_channel();
-        let servers = hosts.into_iter().map(|addr| addr.to_value()).collect();
-        let session_info = (session.session_id, session.session_password.clone());
-        let session_timeout = session.session_timeout;
+        let session_info = session.subscribe_info();
         tokio::spawn(async move {
-            session.serve(servers, sock, buf, connecting_depot, receiver).await;
+            session.serve(sock, buf, connecting_depot, receiver).await;
         });
-        let client = Client::new(chroot.to_owned(), session_info, session_timeout, sender, state_receiver);
+        if let (Some(hosts_sender), Some(requester)) = (tracker, background_requester.clone()) {
+            let tracker = Client::new(OwnedChroot::default(), session_info.clone(), requester, state_receiver.clone());
+            tokio::spawn(ensemble::track_servers(tracker, hosts_sender));
+        }
+        let mut client = Client::new(chroot.to_owned(), session_info, sender, state_receiver);
+        client.request_timeout = self.request_timeout;
+        client.limiter = Arc::new(RequestLimiter::new(self.max_outstanding_requests, self.max_outstanding_bytes));
+        client.fail_fast = self.fail_fast;
+        if let Some(requester) = background_requester.filter(|_| !self.reinit_hooks.is_empty()) {
+            let client = Client { requester, fail_fast: false, ..client.clone() };
+            tokio::spawn(reinit::run_hooks(client, self.reinit_hooks.clone()));
+        }
         Ok(client)
     }
 }
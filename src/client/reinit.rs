@@ -0,0 +1,38 @@
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use super::Client;
+
+type ReinitFn = dyn Fn(Client) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync;
+
+/// Hook to restore states, say, ephemeral nodes and watches, after new session established.
+#[derive(Clone)]
+pub(super) struct ReinitHook(Arc<ReinitFn>);
+
+impl ReinitHook {
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: Fn(Client) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static, {
+        Self(Arc::new(move |client| Box::pin(f(client))))
+    }
+}
+
+impl Debug for ReinitHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("ReinitHook")
+    }
+}
+
+/// Runs hooks in order after every new session established until session terminated.
+pub(super) async fn run_hooks(client: Client, hooks: Vec<ReinitHook>) {
+    let mut session = client.session.clone();
+    while session.changed().await.is_ok() {
+        log::debug!("ZooKeeper session {} established, runs {} reinit hooks", client.session_id(), hooks.len());
+        for hook in hooks.iter() {
+            (hook.0)(client.clone()).await;
+        }
+    }
+}
@@ -7,25 +7,42 @@ use crate::session::{OneshotReceiver, PersistentReceiver, SessionState, WatchRec
 /// StateWatcher tracks session state updates.
 #[derive(Clone, Debug)]
 pub struct StateWatcher {
-    receiver: watch::Receiver<SessionState>,
+    receiver: watch::Receiver<(SessionState, u32)>,
+    reestablishments: u32,
+    reestablished: bool,
 }
 
 impl StateWatcher {
-    pub(super) fn new(receiver: watch::Receiver<SessionState>) -> StateWatcher {
-        StateWatcher { receiver }
+    pub(super) fn new(mut receiver: watch::Receiver<(SessionState, u32)>) -> StateWatcher {
+        let (_, reestablishments) = *receiver.borrow_and_update();
+        StateWatcher { receiver, reestablishments, reestablished: false }
+    }
+
+    /// Creates a new watcher which has consumed latest state.
+    pub(super) fn renew(&self) -> StateWatcher {
+        StateWatcher::new(self.receiver.clone())
     }
 
     /// Returns and consumes most recently state.
+    ///
+    /// [SessionState::Reestablished] is returned once before connected state of new session.
     pub fn state(&mut self) -> SessionState {
-        let state = self.receiver.borrow_and_update();
-        *state
+        let (state, reestablishments) = *self.receiver.borrow_and_update();
+        if state.is_connected() && reestablishments != self.reestablishments {
+            self.reestablishments = reestablishments;
+            self.reestablished = true;
+            return SessionState::Reestablished;
+        }
+        self.reestablished = false;
+        state
     }
 
     /// Waits until state changed and returns consumed state.
     ///
     /// This method will block indefinitely after one of terminal states consumed.
     pub async fn changed(&mut self) -> SessionState {
-        if self.receiver.changed().await.is_err() {
+        // Connected state after reestablishment is not consumed yet.
+        if !self.reestablished && self.receiver.changed().await.is_err() {
             // Terminal state must be deliveried.
             std::future::pending().await
         }
@@ -34,8 +51,21 @@ impl StateWatcher {
 
     /// Returns but not consumes most recently state.
     pub fn peek_state(&self) -> SessionState {
-        let state = self.receiver.borrow();
-        *state
+        let (state, _) = *self.receiver.borrow();
+        state
+    }
+
+    /// Waits until session connected, fails if session terminated.
+    pub(crate) async fn wait_connected(&mut self) -> Result<(), Error> {
+        let mut state = self.state();
+        loop {
+            if state.is_connected() {
+                return Ok(());
+            } else if state.is_terminated() {
+                return Err(state.to_error());
+            }
+            state = self.changed().await;
+        }
     }
 }
 
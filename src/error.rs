@@ -14,6 +14,9 @@ pub enum Error {
     #[error("timeout")]
     Timeout,
 
+    #[error("too many outstanding requests")]
+    TooManyRequests,
+
     #[error("unexpected error: {0}")]
     UnexpectedError(String),
 
@@ -103,7 +106,8 @@ impl Error {
             | Self::MarshallingError
             | Self::Unimplemented
             | Self::ReconfigDisabled
-            | Self::UnexpectedErrorCode(_) => true,
+            | Self::UnexpectedErrorCode(_)
+            | Self::TooManyRequests => true,
             // We are expired anyway, any ephemeral nodes will be deleted by ZooKeeper soon.
             Self::SessionExpired => true,
             // We are closed anyway, the session will expire soon.
@@ -0,0 +1,266 @@
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+
+/// Resolved address of ZooKeeper server.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ServerAddress {
+    /// Host name from which this address is resolved, it is used as server name in tls.
+    pub host: String,
+    pub addr: SocketAddr,
+}
+
+/// Provides server addresses for session to connect to.
+///
+/// Session connects to addresses from one round in order, and asks for next round after all of
+/// them are tried.
+pub trait HostProvider: Send + 'static {
+    /// Returns server addresses to try in next round.
+    fn next_round(&mut self) -> Pin<Box<dyn Future<Output = Vec<ServerAddress>> + Send + '_>>;
+
+    /// Notifies that session has connected to given server.
+    fn connected(&mut self, _server: &ServerAddress) {}
+
+    /// Updates hosts, say, from ensemble config after dynamic reconfiguration.
+    ///
+    /// Returns true if session should reconnect to rebalance load among servers.
+    fn update_hosts(&mut self, _hosts: Vec<(String, u16)>, _connected: Option<&ServerAddress>) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Reconfig {
+    old_hosts: Vec<(String, u16)>,
+    new_hosts: Vec<(String, u16)>,
+    new_probability: f32,
+}
+
+/// Default [HostProvider] which shuffles hosts once like `StaticHostProvider` in Java client and
+/// resolves them to all addresses in every round, so changes to DNS records are picked up.
+#[derive(Clone, Debug)]
+pub struct StaticHostProvider {
+    hosts: Vec<(String, u16)>,
+    reconfig: Option<Reconfig>,
+}
+
+impl StaticHostProvider {
+    pub fn new(mut hosts: Vec<(String, u16)>) -> Self {
+        hosts.shuffle(&mut rand::thread_rng());
+        Self { hosts, reconfig: None }
+    }
+
+    /// Computes probability to migrate to new hosts in same way as `updateServerList` in Java
+    /// client, so load is balanced after reconfiguration. `None` if there is no need to migrate.
+    fn migrate_probability(&self, old_hosts: usize, new_hosts: usize, connected_in_new: bool) -> Option<f32> {
+        let total = (old_hosts + new_hosts) as f32;
+        let size = self.hosts.len();
+        if old_hosts + new_hosts > size {
+            // Load on old servers must be decreased.
+            if !connected_in_new || rand::random::<f32>() <= 1.0 - size as f32 / total {
+                return Some(1.0);
+            }
+            None
+        } else if connected_in_new {
+            // Load on remaining servers are increased, stay connected.
+            None
+        } else if size == old_hosts {
+            // Servers are unchanged but connected one is not among them, it could be removed in
+            // earlier reconfiguration. Java client computes `NaN` here and reconnects to old
+            // servers, so does this.
+            Some(0.0)
+        } else {
+            let old_probability =
+                (old_hosts * (size - old_hosts - new_hosts)) as f32 / (total * (size - old_hosts) as f32);
+            Some(1.0 - old_probability)
+        }
+    }
+
+    /// Matches host from ensemble config against connected server by either host name or address,
+    /// as they could be written differently in connect string and ensemble config.
+    fn is_connected_to((host, port): &(String, u16), server: &ServerAddress) -> bool {
+        if *port != server.addr.port() {
+            return false;
+        }
+        *host == server.host || host.parse::<IpAddr>().map(|ip| ip == server.addr.ip()).unwrap_or(false)
+    }
+
+    async fn resolve(hosts: Vec<(String, u16)>) -> Vec<ServerAddress> {
+        let mut servers = Vec::with_capacity(hosts.len());
+        for (host, port) in hosts.into_iter() {
+            let mut addrs: Vec<_> = match tokio::net::lookup_host((host.as_str(), port)).await {
+                Err(err) => {
+                    log::warn!("ZooKeeper fails to resolve {}:{} due to {}", host, port, err);
+                    continue;
+                },
+                Ok(addrs) => addrs.collect(),
+            };
+            addrs.shuffle(&mut rand::thread_rng());
+            servers.extend(addrs.into_iter().map(|addr| ServerAddress { host: host.clone(), addr }));
+        }
+        servers
+    }
+}
+
+impl HostProvider for StaticHostProvider {
+    fn next_round(&mut self) -> Pin<Box<dyn Future<Output = Vec<ServerAddress>> + Send + '_>> {
+        let hosts = match &self.reconfig {
+            None => self.hosts.clone(),
+            Some(Reconfig { old_hosts, new_hosts, new_probability }) => {
+                let (first, second) = if rand::random::<f32>() < *new_probability {
+                    (new_hosts, old_hosts)
+                } else {
+                    (old_hosts, new_hosts)
+                };
+                first.iter().chain(second.iter()).cloned().collect()
+            },
+        };
+        Box::pin(Self::resolve(hosts))
+    }
+
+    fn connected(&mut self, _server: &ServerAddress) {
+        self.reconfig = None;
+    }
+
+    fn update_hosts(&mut self, mut hosts: Vec<(String, u16)>, connected: Option<&ServerAddress>) -> bool {
+        hosts.shuffle(&mut rand::thread_rng());
+        let connected_in_new =
+            connected.map(|server| hosts.iter().any(|host| Self::is_connected_to(host, server))).unwrap_or(false);
+        let (old_hosts, new_hosts): (Vec<_>, Vec<_>) =
+            hosts.iter().cloned().partition(|host| self.hosts.contains(host));
+        let new_probability = self.migrate_probability(old_hosts.len(), new_hosts.len(), connected_in_new);
+        self.hosts = hosts;
+        self.reconfig = new_probability.map(|new_probability| Reconfig { old_hosts, new_hosts, new_probability });
+        self.reconfig.is_some()
+    }
+}
+
+type HostProviderFn = dyn Fn(Vec<(String, u16)>) -> Box<dyn HostProvider> + Send + Sync;
+
+/// Builds [HostProvider] from hosts in connect string.
+#[derive(Clone)]
+pub(crate) struct HostProviderFactory(Arc<HostProviderFn>);
+
+impl HostProviderFactory {
+    pub fn new<F, P>(f: F) -> Self
+    where
+        F: Fn(Vec<(String, u16)>) -> P + Send + Sync + 'static,
+        P: HostProvider, {
+        Self(Arc::new(move |hosts| Box::new(f(hosts))))
+    }
+
+    pub fn create(&self, hosts: Vec<(String, u16)>) -> Box<dyn HostProvider> {
+        (self.0)(hosts)
+    }
+}
+
+impl Default for HostProviderFactory {
+    fn default() -> Self {
+        Self::new(StaticHostProvider::new)
+    }
+}
+
+impl Debug for HostProviderFactory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("HostProviderFactory")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn server(host: &str, port: u16) -> ServerAddress {
+        ServerAddress { host: host.to_string(), addr: SocketAddr::new(host.parse().unwrap(), port) }
+    }
+
+    #[tokio::test]
+    async fn test_static_host_provider() {
+        let hosts = vec![("127.0.0.1".to_string(), 2181), ("127.0.0.2".to_string(), 2182), ("::1".to_string(), 2183)];
+        let mut provider = StaticHostProvider::new(hosts);
+        let round = provider.next_round().await;
+        let mut sorted = round.clone();
+        sorted.sort_by_key(|server| server.addr.port());
+        assert_eq!(sorted, vec![server("127.0.0.1", 2181), server("127.0.0.2", 2182), server("::1", 2183)]);
+        assert_eq!(provider.next_round().await, round);
+    }
+
+    #[tokio::test]
+    async fn test_static_host_provider_update_hosts() {
+        let hosts =
+            vec![("127.0.0.1".to_string(), 2181), ("127.0.0.2".to_string(), 2182), ("127.0.0.3".to_string(), 2183)];
+        let mut provider = StaticHostProvider::new(hosts.clone());
+        let connected = server("127.0.0.1", 2181);
+
+        // Same hosts.
+        assert_eq!(provider.update_hosts(hosts, Some(&connected)), false);
+
+        // Connected server remains and load is increased.
+        let hosts = vec![("127.0.0.1".to_string(), 2181), ("127.0.0.2".to_string(), 2182)];
+        assert_eq!(provider.update_hosts(hosts, Some(&connected)), false);
+
+        // Connected server is removed, migrate to new servers.
+        let hosts =
+            vec![("127.0.0.2".to_string(), 2182), ("127.0.0.3".to_string(), 2183), ("127.0.0.4".to_string(), 2184)];
+        assert_eq!(provider.update_hosts(hosts, Some(&connected)), true);
+        let mut round = provider.next_round().await;
+        assert_eq!(round.pop(), Some(server("127.0.0.2", 2182)));
+        round.sort_by_key(|server| server.addr.port());
+        assert_eq!(round, vec![server("127.0.0.3", 2183), server("127.0.0.4", 2184)]);
+        provider.connected(&server("127.0.0.3", 2183));
+        assert_eq!(provider.reconfig.is_none(), true);
+    }
+
+    #[test]
+    fn test_static_host_provider_update_hosts_same_size() {
+        let hosts =
+            vec![("127.0.0.1".to_string(), 2181), ("127.0.0.2".to_string(), 2182), ("127.0.0.3".to_string(), 2183)];
+        let mut provider = StaticHostProvider::new(hosts);
+
+        // Connected server is replaced by new one, migrate to new server.
+        let connected = server("127.0.0.3", 2183);
+        let hosts =
+            vec![("127.0.0.1".to_string(), 2181), ("127.0.0.2".to_string(), 2182), ("127.0.0.4".to_string(), 2184)];
+        assert_eq!(provider.update_hosts(hosts.clone(), Some(&connected)), true);
+        let reconfig = provider.reconfig.clone().unwrap();
+        assert_eq!(reconfig.new_hosts, vec![("127.0.0.4".to_string(), 2184)]);
+        assert_eq!(reconfig.new_probability, 1.0);
+
+        // Servers are unchanged but connected server is not among them, reconnect to them.
+        assert_eq!(provider.update_hosts(hosts, Some(&connected)), true);
+        let reconfig = provider.reconfig.clone().unwrap();
+        assert_eq!(reconfig.new_hosts, vec![]);
+        assert_eq!(reconfig.new_probability, 0.0);
+    }
+
+    #[test]
+    fn test_static_host_provider_update_hosts_by_address() {
+        let hosts = vec![("zk1.example.com".to_string(), 2181), ("zk2.example.com".to_string(), 2181)];
+        let mut provider = StaticHostProvider::new(hosts);
+        let connected = ServerAddress {
+            host: "zk1.example.com".to_string(),
+            addr: SocketAddr::new("10.0.0.1".parse().unwrap(), 2181),
+        };
+
+        // Connected server is in ensemble config with ip address, so stay connected.
+        let hosts = vec![("10.0.0.1".to_string(), 2181)];
+        assert_eq!(provider.update_hosts(hosts, Some(&connected)), false);
+
+        // Same address with different port is a different server.
+        let hosts = vec![("10.0.0.1".to_string(), 2182)];
+        assert_eq!(provider.update_hosts(hosts, Some(&connected)), true);
+    }
+
+    #[tokio::test]
+    async fn test_static_host_provider_unresolvable() {
+        let hosts = vec![("host.invalid".to_string(), 2181), ("127.0.0.1".to_string(), 2181)];
+        let mut provider = StaticHostProvider::new(hosts);
+        assert_eq!(provider.next_round().await, vec![server("127.0.0.1", 2181)]);
+    }
+}
@@ -2,11 +2,53 @@ mod acl;
 mod chroot;
 mod client;
 mod error;
+mod host;
 mod proto;
+mod quorum;
+mod recipes;
 mod record;
 mod session;
+#[cfg(feature = "tls")]
+mod tls;
 mod util;
 
 pub use self::acl::{Acl, Acls, AuthId, AuthUser, Permission};
 pub use self::error::Error;
+pub use self::host::{HostProvider, ServerAddress, StaticHostProvider};
+pub use self::quorum::{EnsembleUpdate, QuorumAddress, QuorumConfig, ServerRole, ServerSpec};
+pub use self::recipes::{
+    AtomicValue,
+    Barrier,
+    ChildData,
+    DistributedAtomicLong,
+    DistributedPriorityQueue,
+    DistributedQueue,
+    DoubleBarrier,
+    DoubleBarrierMember,
+    InterProcessMutex,
+    InterProcessMutexGuard,
+    InterProcessSemaphore,
+    LeaderEvent,
+    LeaderLatch,
+    Lease,
+    NodeCache,
+    PathChildrenCache,
+    PathChildrenCacheEvent,
+    PersistentEphemeralNode,
+    RecipeOptions,
+    TreeCache,
+    TreeCacheEvent,
+};
+#[cfg(feature = "discovery")]
+pub use self::recipes::{
+    ProviderStrategy,
+    ServiceDiscovery,
+    ServiceInstance,
+    ServiceProvider,
+    ServiceType,
+    UriSpec,
+    UriSpecPart,
+};
+#[cfg(feature = "tls")]
+pub use self::tls::TlsOptions;
 pub use crate::client::*;
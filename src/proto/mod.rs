@@ -11,6 +11,7 @@ mod path;
 mod reconfig;
 mod reply_header;
 mod request_header;
+mod sasl;
 mod stat;
 
 use bytes::BufMut;
@@ -37,9 +38,10 @@ pub use self::data::{
 pub use self::error_code::ErrorCode;
 pub use self::multi::{MultiHeader, MultiReadResponse, MultiWriteResponse};
 pub use self::op_code::OpCode;
-pub use self::reconfig::{EnsembleUpdate, ReconfigRequest};
+pub use self::reconfig::ReconfigRequest;
 pub use self::reply_header::ReplyHeader;
 pub use self::request_header::RequestHeader;
+pub use self::sasl::SaslRequest;
 pub use self::stat::Stat;
 use super::record::{DynamicRecord, SerializableRecord};
 use crate::record::Record;
@@ -2,83 +2,46 @@ use bytes::BufMut;
 
 use crate::record::{DynamicRecord, SerializableRecord};
 
-struct ServerList<'a, 'b: 'a, T: Iterator<Item = &'b str> + Clone>(&'a T);
+/// Comma separated server list, empty list is serialized as null.
+struct ServerList<'a>(&'a str);
 
-impl<'a, 'b, T: Iterator<Item = &'b str> + Clone> SerializableRecord for ServerList<'a, 'b, T> {
+impl SerializableRecord for ServerList<'_> {
     fn serialize(&self, buf: &mut dyn BufMut) {
-        let n = self.serialized_len();
-        if n == 4 {
+        if self.0.is_empty() {
             buf.put_i32(-1);
             return;
         }
-        buf.put_i32(n as i32 - 4);
-        self.0.clone().filter(|s| !s.is_empty()).enumerate().for_each(|(i, s)| {
-            if i != 0 {
-                buf.put_u8(b',');
-            }
-            buf.put_slice(s.as_bytes());
-        });
+        self.0.serialize(buf);
     }
 }
 
-impl<'a, 'b, T: Iterator<Item = &'b str> + Clone> DynamicRecord for ServerList<'a, 'b, T> {
+impl DynamicRecord for ServerList<'_> {
     fn serialized_len(&self) -> usize {
-        let n: usize = self.0.clone().filter(|s| !s.is_empty()).map(|s| s.len() + 1).sum();
-        4 + if n > 0 { n - 1 } else { 0 }
+        4 + self.0.len()
     }
 }
 
-/// EnsembleUpdate specifies an update to ZooKeeper ensemble membership.
-///
-/// The item could be single server or comma separated server list.
-pub enum EnsembleUpdate<'a, T: Iterator<Item = &'a str> + Clone> {
-    Incremental {
-        /// Joining servers.
-        joinings: T,
-
-        /// Leaving servers.
-        leavings: T,
-    },
-    New {
-        /// New ensemble.
-        ensemble: T,
-    },
-}
-
-pub struct ReconfigRequest<'a, T: Iterator<Item = &'a str> + Clone> {
-    pub update: EnsembleUpdate<'a, T>,
+pub struct ReconfigRequest<'a> {
+    pub joining_servers: &'a str,
+    pub leaving_servers: &'a str,
+    pub new_members: &'a str,
     pub version: i64,
 }
 
-impl<'a, T: Iterator<Item = &'a str> + Clone> SerializableRecord for ReconfigRequest<'a, T> {
+impl SerializableRecord for ReconfigRequest<'_> {
     fn serialize(&self, buf: &mut dyn BufMut) {
-        match &self.update {
-            EnsembleUpdate::Incremental { joinings, leavings } => {
-                ServerList(joinings).serialize(buf);
-                ServerList(leavings).serialize(buf);
-                buf.put_i32(-1);
-            },
-            EnsembleUpdate::New { ensemble } => {
-                buf.put_i32(-1);
-                buf.put_i32(-1);
-                ServerList(ensemble).serialize(buf);
-            },
-        };
+        ServerList(self.joining_servers).serialize(buf);
+        ServerList(self.leaving_servers).serialize(buf);
+        ServerList(self.new_members).serialize(buf);
         self.version.serialize(buf);
     }
 }
 
-impl<'a, T: Iterator<Item = &'a str> + Clone> DynamicRecord for ReconfigRequest<'a, T> {
+impl DynamicRecord for ReconfigRequest<'_> {
     fn serialized_len(&self) -> usize {
-        let n = match &self.update {
-            EnsembleUpdate::Incremental { joinings, leavings } => {
-                4 + ServerList(joinings).serialized_len() + ServerList(leavings).serialized_len()
-            },
-            EnsembleUpdate::New { ensemble } => {
-                let servers = ServerList(ensemble);
-                8 + servers.serialized_len()
-            },
-        };
-        n + 8
+        ServerList(self.joining_servers).serialized_len()
+            + ServerList(self.leaving_servers).serialized_len()
+            + ServerList(self.new_members).serialized_len()
+            + 8
     }
 }
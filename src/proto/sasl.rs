@@ -0,0 +1,19 @@
+use bytes::BufMut;
+
+use crate::record::{DynamicRecord, SerializableRecord};
+
+pub struct SaslRequest<'a> {
+    pub token: &'a [u8],
+}
+
+impl SerializableRecord for SaslRequest<'_> {
+    fn serialize(&self, buf: &mut dyn BufMut) {
+        self.token.serialize(buf);
+    }
+}
+
+impl DynamicRecord for SaslRequest<'_> {
+    fn serialized_len(&self) -> usize {
+        self.token.serialized_len()
+    }
+}
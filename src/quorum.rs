@@ -0,0 +1,393 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// Role of server in ZooKeeper ensemble.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ServerRole {
+    /// Server votes in leader election and quorum writes.
+    #[default]
+    Participant,
+
+    /// Server replicates state but does not vote.
+    Observer,
+}
+
+impl Display for ServerRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerRole::Participant => f.write_str("participant"),
+            ServerRole::Observer => f.write_str("observer"),
+        }
+    }
+}
+
+impl FromStr for ServerRole {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "participant" => Ok(ServerRole::Participant),
+            "observer" => Ok(ServerRole::Observer),
+            _ => Err(Error::BadArguments(&"invalid server role")),
+        }
+    }
+}
+
+/// Address of server for quorum communication and leader election.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QuorumAddress {
+    pub host: String,
+    pub quorum_port: u16,
+    pub election_port: u16,
+}
+
+impl QuorumAddress {
+    pub fn new(host: impl Into<String>, quorum_port: u16, election_port: u16) -> Self {
+        Self { host: host.into(), quorum_port, election_port }
+    }
+}
+
+impl Display for QuorumAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", Host(&self.host), self.quorum_port, self.election_port)
+    }
+}
+
+/// Host which is bracketed if it is an ipv6 address.
+struct Host<'a>(&'a str);
+
+impl Display for Host<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.contains(':') {
+            write!(f, "[{}]", self.0)
+        } else {
+            f.write_str(self.0)
+        }
+    }
+}
+
+/// Splits host, which could be bracketed, from port and remaining parts.
+fn split_host(s: &str) -> Result<(&str, &str), Error> {
+    let (host, remains) = match s.strip_prefix('[') {
+        None => s.split_once(':').unwrap_or((s, "")),
+        Some(s) => match s.split_once(']') {
+            None => return Err(Error::BadArguments(&"unclosed bracket in server address")),
+            Some((host, "")) => (host, ""),
+            Some((host, remains)) => match remains.strip_prefix(':') {
+                None => return Err(Error::BadArguments(&"invalid server address")),
+                Some(remains) => (host, remains),
+            },
+        },
+    };
+    if host.is_empty() {
+        return Err(Error::BadArguments(&"empty host in server address"));
+    }
+    Ok((host, remains))
+}
+
+fn parse_port(s: &str) -> Result<u16, Error> {
+    match s.parse::<u16>() {
+        Ok(port) if port != 0 => Ok(port),
+        _ => Err(Error::BadArguments(&"invalid port in server address")),
+    }
+}
+
+/// Server specification in ZooKeeper ensemble config.
+///
+/// It is in format `server.<id>=<address>[|<address>]...[:<role>][;[<client address>:]<client port>]`,
+/// where `<address>` is `<host>:<quorum port>:<election port>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerSpec {
+    pub id: u64,
+    /// Addresses for quorum communication and leader election, multiple addresses are supported
+    /// since ZooKeeper 3.6.
+    pub addresses: Vec<QuorumAddress>,
+    pub role: ServerRole,
+    /// Client address, host is `0.0.0.0` if only client port is specified.
+    pub client_address: Option<(String, u16)>,
+}
+
+impl ServerSpec {
+    const PREFIX: &'static str = "server.";
+
+    pub fn new(id: u64, address: QuorumAddress) -> Self {
+        Self { id, addresses: vec![address], role: ServerRole::Participant, client_address: None }
+    }
+
+    pub fn with_role(mut self, role: ServerRole) -> Self {
+        self.role = role;
+        self
+    }
+
+    pub fn with_client_address(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.client_address = Some((host.into(), port));
+        self
+    }
+
+    /// Address for clients to connect to.
+    ///
+    /// Wildcard client host, say `0.0.0.0` and `::`, is replaced by host of first quorum address.
+    pub fn connect_address(&self) -> Option<(&str, u16)> {
+        let (host, port) = self.client_address.as_ref()?;
+        match host.as_str() {
+            "" | "0.0.0.0" | "::" | "0:0:0:0:0:0:0:0" => Some((self.addresses.first()?.host.as_str(), *port)),
+            host => Some((host, *port)),
+        }
+    }
+
+    fn parse_address(s: &str) -> Result<(QuorumAddress, Option<ServerRole>), Error> {
+        let (host, remains) = split_host(s)?;
+        let mut parts = remains.split(':');
+        let quorum_port = parse_port(parts.next().unwrap_or_default())?;
+        let election_port = match parts.next() {
+            None => return Err(Error::BadArguments(&"no election port in server address")),
+            Some(port) => parse_port(port)?,
+        };
+        let role = parts.next().map(ServerRole::from_str).transpose()?;
+        if parts.next().is_some() {
+            return Err(Error::BadArguments(&"unexpected trailing part in server address"));
+        }
+        Ok((QuorumAddress::new(host, quorum_port, election_port), role))
+    }
+
+    fn parse_client_address(s: &str) -> Result<(String, u16), Error> {
+        if !s.contains(':') {
+            return Ok(("0.0.0.0".to_string(), parse_port(s)?));
+        }
+        let (host, port) = split_host(s)?;
+        Ok((host.to_string(), parse_port(port)?))
+    }
+}
+
+impl Display for ServerSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}=", Self::PREFIX, self.id)?;
+        for (i, address) in self.addresses.iter().enumerate() {
+            if i != 0 {
+                f.write_str("|")?;
+            }
+            write!(f, "{}", address)?;
+        }
+        write!(f, ":{}", self.role)?;
+        if let Some((host, port)) = &self.client_address {
+            write!(f, ";{}:{}", Host(host), port)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ServerSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (key, value) = s.trim().split_once('=').ok_or(Error::BadArguments(&"no `=` in server spec"))?;
+        let id = match key.strip_prefix(Self::PREFIX).map(u64::from_str) {
+            Some(Ok(id)) => id,
+            _ => return Err(Error::BadArguments(&"invalid server id")),
+        };
+        let (addresses, client_address) = match value.split_once(';') {
+            None => (value, None),
+            Some((addresses, client_address)) => (addresses, Some(Self::parse_client_address(client_address)?)),
+        };
+        let mut role = None;
+        let addresses = addresses
+            .split('|')
+            .map(|address| {
+                let (address, address_role) = Self::parse_address(address)?;
+                // Role is only allowed on last address.
+                if role.is_some() {
+                    return Err(Error::BadArguments(&"server role must follow last address"));
+                }
+                role = address_role;
+                Ok(address)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(ServerSpec { id, addresses, role: role.unwrap_or_default(), client_address })
+    }
+}
+
+/// Ensemble config of ZooKeeper, that is data of node "/zookeeper/config".
+///
+/// It parses and formats config in the format of [crate::Client::get_config].
+///
+/// ```
+/// use zookeeper_client::QuorumConfig;
+///
+/// let config: QuorumConfig = "server.1=zoo1:2888:3888:participant;0.0.0.0:2181\nversion=100000000".parse().unwrap();
+/// assert_eq!(config.servers[0].connect_address(), Some(("zoo1", 2181)));
+/// assert_eq!(config.version, Some(0x100000000));
+/// assert_eq!(config.to_string(), "server.1=zoo1:2888:3888:participant;0.0.0.0:2181\nversion=100000000");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuorumConfig {
+    pub servers: Vec<ServerSpec>,
+    /// Properties other than servers and version, say, `group.<id>` and `weight.<id>` in
+    /// hierarchical quorum.
+    pub properties: Vec<(String, String)>,
+    /// Version of config, it is the zxid in which config is committed.
+    pub version: Option<i64>,
+}
+
+impl QuorumConfig {
+    /// Parses config from data of config node.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let config = std::str::from_utf8(data).map_err(|_| Error::BadArguments(&"config is not valid utf8"))?;
+        config.parse()
+    }
+}
+
+impl Display for QuorumConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let servers = self.servers.iter().map(|server| server.to_string());
+        let properties = self.properties.iter().map(|(key, value)| format!("{}={}", key, value));
+        let version = self.version.iter().map(|version| format!("version={:x}", version));
+        for (i, line) in servers.chain(properties).chain(version).enumerate() {
+            if i != 0 {
+                f.write_str("\n")?;
+            }
+            f.write_str(&line)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for QuorumConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut config = QuorumConfig::default();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if line.starts_with(ServerSpec::PREFIX) {
+                config.servers.push(line.parse()?);
+                continue;
+            }
+            match line.split_once('=') {
+                None => return Err(Error::BadArguments(&"no `=` in config line")),
+                Some(("version", version)) => match i64::from_str_radix(version, 16) {
+                    Err(_) => return Err(Error::BadArguments(&"invalid config version")),
+                    Ok(version) => config.version = Some(version),
+                },
+                Some((key, value)) => config.properties.push((key.to_string(), value.to_string())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// EnsembleUpdate specifies an update to ZooKeeper ensemble membership.
+pub enum EnsembleUpdate<'a> {
+    Incremental {
+        /// Joining servers.
+        joinings: &'a [ServerSpec],
+
+        /// Ids of leaving servers.
+        leavings: &'a [u64],
+    },
+    New {
+        /// New ensemble.
+        ensemble: &'a [ServerSpec],
+    },
+}
+
+impl EnsembleUpdate<'_> {
+    fn join<T: Display>(items: &[T]) -> String {
+        items.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(",")
+    }
+
+    /// Formats to joining servers, leaving servers and new members in reconfig request.
+    pub(crate) fn to_server_lists(&self) -> (String, String, String) {
+        match self {
+            EnsembleUpdate::Incremental { joinings, leavings } => {
+                (Self::join(joinings), Self::join(leavings), Default::default())
+            },
+            EnsembleUpdate::New { ensemble } => (Default::default(), Default::default(), Self::join(ensemble)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("server.1=zoo1:2888:3888:participant;0.0.0.0:2181"; "wildcard")]
+    #[test_case("server.2=zoo2:2888:3888:observer;zk2.example.com:2182"; "observer")]
+    #[test_case("server.3=[2001:db8::3]:2888:3888:participant;[::]:2183"; "ipv6")]
+    #[test_case("server.4=zoo4:2888:3888|10.0.0.4:2889:3889:participant;10.0.0.4:2184"; "multiple addresses")]
+    #[test_case("server.5=zoo5:2888:3888:participant"; "no client address")]
+    fn test_server_spec_round_trip(s: &str) {
+        let server: ServerSpec = s.parse().unwrap();
+        assert_eq!(server.to_string(), s);
+    }
+
+    #[test]
+    fn test_server_spec_parse() {
+        let server: ServerSpec = "server.5=zoo5:2888:3888;2185".parse().unwrap();
+        assert_eq!(
+            server,
+            ServerSpec::new(5, QuorumAddress::new("zoo5", 2888, 3888)).with_client_address("0.0.0.0", 2185)
+        );
+        assert_eq!(server.connect_address(), Some(("zoo5", 2185)));
+        assert_eq!(server.to_string(), "server.5=zoo5:2888:3888:participant;0.0.0.0:2185");
+
+        let server: ServerSpec = "server.3=[2001:db8::3]:2888:3888:observer;[::]:2183".parse().unwrap();
+        assert_eq!(server.addresses, vec![QuorumAddress::new("2001:db8::3", 2888, 3888)]);
+        assert_eq!(server.role, ServerRole::Observer);
+        assert_eq!(server.connect_address(), Some(("2001:db8::3", 2183)));
+    }
+
+    #[test_case("server.x=zoo1:2888:3888"; "invalid id")]
+    #[test_case("zoo1:2888:3888"; "no id")]
+    #[test_case("server.1=zoo1:2888"; "no election port")]
+    #[test_case("server.1=zoo1:2888:3888:leader"; "invalid role")]
+    #[test_case("server.1=zoo1:2888:3888:participant|zoo2:2888:3888"; "role in middle")]
+    #[test_case("server.1=[::1:2888:3888"; "unclosed bracket")]
+    #[test_case("server.1=zoo1:2888:3888;zoo1:0"; "invalid client port")]
+    fn test_server_spec_parse_invalid(s: &str) {
+        assert!(s.parse::<ServerSpec>().is_err());
+    }
+
+    #[test]
+    fn test_quorum_config() {
+        let s = "server.1=zoo1:2888:3888:participant;0.0.0.0:2181
+server.2=zoo2:2888:3888:observer;0.0.0.0:2181
+group.1=1:2
+weight.1=1
+version=10000000a";
+        let config = QuorumConfig::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(config.servers.len(), 2);
+        assert_eq!(config.properties, vec![
+            ("group.1".to_string(), "1:2".to_string()),
+            ("weight.1".to_string(), "1".to_string())
+        ]);
+        assert_eq!(config.version, Some(0x10000000a));
+        assert_eq!(config.to_string(), s);
+        assert_eq!(config.to_string().parse::<QuorumConfig>().unwrap(), config);
+    }
+
+    #[test]
+    fn test_ensemble_update() {
+        let servers = [
+            ServerSpec::new(1, QuorumAddress::new("zoo1", 2888, 3888)).with_client_address("zoo1", 2181),
+            ServerSpec::new(2, QuorumAddress::new("zoo2", 2888, 3888))
+                .with_role(ServerRole::Observer)
+                .with_client_address("zoo2", 2181),
+        ];
+        assert_eq!(
+            EnsembleUpdate::New { ensemble: &servers }.to_server_lists(),
+            (
+                "".to_string(),
+                "".to_string(),
+                "server.1=zoo1:2888:3888:participant;zoo1:2181,server.2=zoo2:2888:3888:observer;zoo2:2181".to_string()
+            )
+        );
+        assert_eq!(
+            EnsembleUpdate::Incremental { joinings: &servers[1..], leavings: &[3, 4] }.to_server_lists(),
+            ("server.2=zoo2:2888:3888:observer;zoo2:2181".to_string(), "3,4".to_string(), "".to_string())
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use super::{OwnedCreateOptions, OwnedRecipeOptions, RecipeOptions};
+use crate::acl::Acls;
+use crate::client::{Client, CreateMode, LockPrefix, Stat};
+use crate::error::Error;
+use crate::util;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Result of modification to [DistributedAtomicLong].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AtomicValue {
+    /// Whether modification succeeded.
+    pub succeeded: bool,
+
+    /// Value before modification.
+    pub pre_value: i64,
+
+    /// Value after modification, it is same as `pre_value` if modification failed.
+    pub post_value: i64,
+}
+
+/// Counter compatible with `DistributedAtomicLong` in Apache Curator.
+///
+/// Value is stored as 8-byte big-endian integer, absent node is treated as zero. Modifications
+/// are optimistic writes with expected version, which are retried on concurrent updates up to
+/// given attempts. Optionally, modifications are retried again after acquiring a lock, similar to
+/// `PromotedToLock` in Curator.
+///
+/// # Error handling on [Error::ConnectionLoss]
+/// Modification is not retried on connection loss as it is unknown whether it has been applied.
+#[derive(Clone, Debug)]
+pub struct DistributedAtomicLong {
+    client: Client,
+    path: String,
+    attempts: usize,
+    options: OwnedRecipeOptions,
+    lock: Option<String>,
+}
+
+impl DistributedAtomicLong {
+    const DEFAULT_ATTEMPTS: usize = 10;
+
+    /// Constructs counter on given path.
+    ///
+    /// [RecipeOptions] specifies acls for counter node and options to create its ancestors if they
+    /// are absent. They also apply to lock nodes and lock directory if promoted to lock, see
+    /// [DistributedAtomicLong::with_promoted_lock].
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `path` is not a valid path.
+    /// * [Error::InvalidAcl] if acl is empty.
+    pub fn new<'a>(client: &Client, path: &str, options: impl Into<RecipeOptions<'a>>) -> Result<Self> {
+        util::validate_path(Default::default(), path, false)?;
+        let options = options.into();
+        if options.acls.is_empty() {
+            return Err(Error::InvalidAcl);
+        }
+        Ok(Self {
+            client: client.clone(),
+            path: path.to_string(),
+            attempts: Self::DEFAULT_ATTEMPTS,
+            options: OwnedRecipeOptions::new(&options),
+            lock: None,
+        })
+    }
+
+    /// Specifies maximum attempts of optimistic writes for one modification, defaults to 10.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `attempts` is zero.
+    pub fn with_attempts(mut self, attempts: usize) -> Result<Self> {
+        if attempts == 0 {
+            return Err(Error::BadArguments(&"atomic long must have at least one attempt"));
+        }
+        self.attempts = attempts;
+        Ok(self)
+    }
+
+    /// Retries modification after acquiring lock under given directory if optimistic attempts
+    /// failed. All participants should use same lock directory.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `dir` is not a valid path.
+    pub fn with_promoted_lock(mut self, dir: &str) -> Result<Self> {
+        LockPrefix::new_curator(dir, "lock-")?;
+        self.lock = Some(dir.to_string());
+        Ok(self)
+    }
+
+    fn parse(data: &[u8]) -> Result<i64> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        match <[u8; 8]>::try_from(data) {
+            Ok(bytes) => Ok(i64::from_be_bytes(bytes)),
+            Err(_) => Err(Error::UnexpectedError(format!("atomic long expects 8 bytes but got {}", data.len()))),
+        }
+    }
+
+    /// Reads value and stat, stat is `None` if node does not exist.
+    async fn read(&self) -> Result<(i64, Option<Stat>)> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.get_data(&self.path).await {
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(Error::NoNode) => return Ok((0, None)),
+                Err(err) => return Err(err),
+                Ok((data, stat)) => return Ok((Self::parse(&data)?, Some(stat))),
+            }
+        }
+    }
+
+    /// Writes value if node is not changed since read, returns false otherwise.
+    async fn write(&self, value: i64, stat: Option<&Stat>) -> Result<bool> {
+        let data = value.to_be_bytes();
+        if let Some(stat) = stat {
+            return match self.client.set_data(&self.path, &data, Some(stat.version)).await {
+                Ok(_) => Ok(true),
+                Err(Error::NoNode | Error::BadVersion) => Ok(false),
+                Err(err) => Err(err),
+            };
+        }
+        let options = CreateMode::Persistent.with_acls(Acls::new(&self.options.acls));
+        loop {
+            match self.client.create(&self.path, &data, &options).await {
+                Ok(_) => return Ok(true),
+                Err(Error::NodeExists) => return Ok(false),
+                Err(Error::NoNode) => {
+                    let Some(options) = self.options.parent.as_ref().map(OwnedCreateOptions::to_options) else {
+                        return Err(Error::NoNode);
+                    };
+                    let (parent, _, _) = util::split_path(&self.path);
+                    match self.client.create_ancestor_backword(parent, &options).await {
+                        Ok(_) | Err(Error::NodeExists) => continue,
+                        Err(err) => return Err(err),
+                    }
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Applies `f` to value optimistically, `None` from `f` fails modification immediately.
+    async fn try_modify(&self, f: &impl Fn(i64) -> Option<i64>) -> Result<AtomicValue> {
+        let mut pre_value = 0;
+        for _ in 0..self.attempts {
+            let (value, stat) = self.read().await?;
+            pre_value = value;
+            let Some(post_value) = f(value) else {
+                break;
+            };
+            if self.write(post_value, stat.as_ref()).await? {
+                return Ok(AtomicValue { succeeded: true, pre_value, post_value });
+            }
+        }
+        Ok(AtomicValue { succeeded: false, pre_value, post_value: pre_value })
+    }
+
+    async fn modify(&self, f: impl Fn(i64) -> Option<i64>) -> Result<AtomicValue> {
+        let value = self.try_modify(&f).await?;
+        // Rejected modification is not retried.
+        if value.succeeded || f(value.pre_value).is_none() {
+            return Ok(value);
+        }
+        let Some(dir) = &self.lock else {
+            return Ok(value);
+        };
+        let prefix = LockPrefix::new_curator(dir, "lock-")?;
+        let _lock = self.client.lock(prefix, Default::default(), self.options.to_lock_options()).await?;
+        self.try_modify(&f).await
+    }
+
+    /// Gets current value.
+    pub async fn get(&self) -> Result<i64> {
+        let (value, _) = self.read().await?;
+        Ok(value)
+    }
+
+    /// Adds given delta to value, wraps around on overflow.
+    pub async fn add(&self, delta: i64) -> Result<AtomicValue> {
+        self.modify(|value| Some(value.wrapping_add(delta))).await
+    }
+
+    /// Adds one to value.
+    pub async fn increment(&self) -> Result<AtomicValue> {
+        self.add(1).await
+    }
+
+    /// Subtracts one from value.
+    pub async fn decrement(&self) -> Result<AtomicValue> {
+        self.add(-1).await
+    }
+
+    /// Sets value to `new_value` if current value equals to `expected_value`.
+    pub async fn compare_and_set(&self, expected_value: i64, new_value: i64) -> Result<AtomicValue> {
+        self.modify(|value| if value == expected_value { Some(new_value) } else { None }).await
+    }
+
+    /// Sets value to `new_value`.
+    pub async fn try_set(&self, new_value: i64) -> Result<AtomicValue> {
+        self.modify(|_| Some(new_value)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(DistributedAtomicLong::parse(&[]).unwrap(), 0);
+        assert_eq!(DistributedAtomicLong::parse(&5i64.to_be_bytes()).unwrap(), 5);
+        assert_eq!(DistributedAtomicLong::parse(&(-2i64).to_be_bytes()).unwrap(), -2);
+        assert!(DistributedAtomicLong::parse(&[1, 2, 3]).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use super::{OwnedCreateOptions, OwnedRecipeOptions, RecipeOptions};
+use crate::acl::Acls;
+use crate::client::{Client, CreateMode, CreateOptions};
+use crate::error::Error;
+use crate::session::EventType;
+use crate::util;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const READY_NODE: &str = "ready";
+
+/// Barrier which blocks participants until its node is deleted, similar to `DistributedBarrier`
+/// in Apache Curator.
+#[derive(Clone, Debug)]
+pub struct Barrier {
+    client: Client,
+    path: String,
+}
+
+impl Barrier {
+    /// Constructs barrier on given path.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `path` is not a valid path.
+    pub fn new(client: &Client, path: &str) -> Result<Self> {
+        util::validate_path(Default::default(), path, false)?;
+        Ok(Self { client: client.clone(), path: path.to_string() })
+    }
+
+    /// Path of barrier node.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Sets barrier by creating its node, it is fine for the node to exist already.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if [CreateMode] is sequential.
+    /// * [Error::NoNode] if parent node does not exist.
+    pub async fn set(&self, options: &CreateOptions<'_>) -> Result<()> {
+        if options.mode.is_sequential() {
+            return Err(Error::BadArguments(&"barrier node must not be sequential"));
+        }
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.create(&self.path, Default::default(), options).await {
+                Ok(_) | Err(Error::NodeExists) => return Ok(()),
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Removes barrier by deleting its node, it is fine for the node to be absent already.
+    pub async fn remove(&self) -> Result<()> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.delete(&self.path, None).await {
+                Ok(_) | Err(Error::NoNode) => return Ok(()),
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Waits until barrier removed.
+    ///
+    /// # Cancellation safety
+    /// This method is cancellation safe.
+    pub async fn wait(&self) -> Result<()> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            let watcher = match self.client.check_and_watch_stat(&self.path).await {
+                Err(Error::ConnectionLoss) => {
+                    state_watcher.wait_connected().await?;
+                    continue;
+                },
+                Err(err) => return Err(err),
+                Ok((None, _)) => return Ok(()),
+                Ok((Some(_), watcher)) => watcher,
+            };
+            if watcher.changed().await.event_type == EventType::NodeDeleted {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Double barrier which enables participants to start and end computation together, compatible
+/// with `DistributedDoubleBarrier` in Apache Curator.
+///
+/// Participants enter by creating ephemeral nodes under barrier node, and proceed after given
+/// number of participants entered. They leave by deleting their nodes, and proceed after all
+/// participants left.
+#[derive(Clone, Debug)]
+pub struct DoubleBarrier {
+    client: Client,
+    path: String,
+    ready_path: String,
+    members: usize,
+    options: OwnedRecipeOptions,
+}
+
+/// Participant of [DoubleBarrier], its node is deleted in background when dropped.
+#[derive(Debug)]
+pub struct DoubleBarrierMember {
+    client: Client,
+    barrier: String,
+    path: String,
+}
+
+impl DoubleBarrier {
+    /// Constructs double barrier on given path for given number of participants.
+    ///
+    /// [RecipeOptions] specifies acls for barrier node and participant nodes, and options to create
+    /// barrier node and its ancestors if they are absent.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `path` is not a valid path or `members` is zero.
+    /// * [Error::InvalidAcl] if acl is empty.
+    pub fn new<'a>(client: &Client, path: &str, members: usize, options: impl Into<RecipeOptions<'a>>) -> Result<Self> {
+        util::validate_path(Default::default(), path, false)?;
+        if members == 0 {
+            return Err(Error::BadArguments(&"double barrier must have at least one member"));
+        }
+        let options = options.into();
+        if options.acls.is_empty() {
+            return Err(Error::InvalidAcl);
+        }
+        Ok(Self {
+            client: client.clone(),
+            ready_path: format!("{}/{}", path, READY_NODE),
+            path: path.to_string(),
+            members,
+            options: OwnedRecipeOptions::new(&options),
+        })
+    }
+
+    fn acls(&self) -> Acls<'_> {
+        Acls::new(&self.options.acls)
+    }
+
+    async fn create(&self, path: &str, mode: CreateMode) -> Result<()> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.create(path, Default::default(), &mode.with_acls(self.acls())).await {
+                Ok(_) | Err(Error::NodeExists) => return Ok(()),
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(Error::NoNode) => {
+                    let Some(options) = self.options.parent.as_ref().map(OwnedCreateOptions::to_options) else {
+                        return Err(Error::NoNode);
+                    };
+                    self.client.create_ancestor_backword(&self.path, &options).await?;
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Enters barrier and waits until given number of participants entered.
+    ///
+    /// # Cancellation safety
+    /// This method is cancellation safe, node created in entering is deleted in background.
+    pub async fn enter(&self) -> Result<DoubleBarrierMember> {
+        let member = DoubleBarrierMember {
+            client: self.client.clone(),
+            barrier: self.path.clone(),
+            path: format!("{}/{}", self.path, uuid::Uuid::new_v4()),
+        };
+        self.create(&member.path, CreateMode::Ephemeral).await?;
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            let watcher = match self.client.check_and_watch_stat(&self.ready_path).await {
+                Err(Error::ConnectionLoss) => {
+                    state_watcher.wait_connected().await?;
+                    continue;
+                },
+                Err(err) => return Err(err),
+                Ok((Some(_), _)) => return Ok(member),
+                Ok((None, watcher)) => watcher,
+            };
+            let children = member.list_participants().await?;
+            if children.len() >= self.members {
+                self.create(&self.ready_path, CreateMode::Persistent).await?;
+                return Ok(member);
+            }
+            watcher.changed().await;
+        }
+    }
+}
+
+impl DoubleBarrierMember {
+    /// Path of participant node.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Lists participants in sorted order.
+    async fn list_participants(&self) -> Result<Vec<String>> {
+        let mut state_watcher = self.client.state_watcher();
+        let mut children = loop {
+            match self.client.list_children(&self.barrier).await {
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                result => break result?,
+            }
+        };
+        children.retain(|child| child != READY_NODE);
+        children.sort_unstable();
+        Ok(children)
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.delete(path, None).await {
+                Ok(_) | Err(Error::NoNode) => return Ok(()),
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Leaves barrier and waits until all participants left.
+    ///
+    /// # Notable errors
+    /// * [Error::RuntimeInconsistent] if participant node is deleted by others.
+    ///
+    /// # Cancellation safety
+    /// This method is cancellation safe, participant node is deleted in background.
+    pub async fn leave(mut self) -> Result<()> {
+        self.wait_leave().await?;
+        self.path.clear();
+        Ok(())
+    }
+
+    async fn wait_leave(&self) -> Result<()> {
+        let path = self.path.as_str();
+        let (_, _, name) = util::split_path(path);
+        let mut deleted = false;
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            let children = self.list_participants().await?;
+            let Some(first) = children.first() else {
+                return self.delete(&format!("{}/{}", self.barrier, READY_NODE)).await;
+            };
+            let position = children.iter().position(|child| child == name);
+            let watching = match position {
+                None if !deleted => {
+                    // Not ours, so leave it to owner.
+                    return Err(Error::RuntimeInconsistent);
+                },
+                Some(_) if children.len() == 1 => {
+                    self.delete(path).await?;
+                    deleted = true;
+                    continue;
+                },
+                // Lowest participant waits for others to leave.
+                Some(0) => children.last().unwrap(),
+                _ => {
+                    if !deleted {
+                        self.delete(path).await?;
+                        deleted = true;
+                    }
+                    first
+                },
+            };
+            let watching = format!("{}/{}", self.barrier, watching);
+            match self.client.check_and_watch_stat(&watching).await {
+                Err(Error::ConnectionLoss) => {
+                    state_watcher.wait_connected().await?;
+                    continue;
+                },
+                Err(err) => return Err(err),
+                Ok((None, _)) => continue,
+                Ok((Some(_), watcher)) => watcher.changed().await,
+            };
+        }
+    }
+}
+
+/// Deletes participant node in background.
+impl Drop for DoubleBarrierMember {
+    fn drop(&mut self) {
+        if self.path.is_empty() {
+            return;
+        }
+        let path = std::mem::take(&mut self.path);
+        self.client.clone().delete_background(path);
+    }
+}
@@ -0,0 +1,143 @@
+use tokio::select;
+use tokio::sync::{oneshot, watch};
+
+use crate::client::{Client, OneshotWatcher, StateWatcher};
+use crate::error::Error;
+use crate::proto::Stat;
+use crate::session::{EventType, SessionState};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Data and stat of node, `None` if node does not exist.
+type NodeState = Option<(Vec<u8>, Stat)>;
+
+/// Cache which keeps data and stat of node current, similar to `NodeCache` in Apache Curator.
+///
+/// Node is refetched after reconnection, so changes during disconnection are caught up. Cache is
+/// not updated anymore after session terminated, say, expired without recreation.
+#[derive(Debug)]
+pub struct NodeCache {
+    path: String,
+    state: watch::Receiver<NodeState>,
+    _closer: oneshot::Sender<()>,
+}
+
+impl NodeCache {
+    /// Fetches node of given path and keeps it current in background until this instance dropped.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `path` is not a valid path.
+    /// * [Error::NoAuth] if node is not readable.
+    pub async fn new(client: &Client, path: &str) -> Result<Self> {
+        let (sender, receiver) = watch::channel(None);
+        let mut updater = CacheUpdater {
+            client: client.clone(),
+            state_watcher: client.state_watcher(),
+            path: path.to_string(),
+            sender,
+        };
+        let watcher = updater.fetch().await?;
+        let (closer, closing) = oneshot::channel();
+        tokio::spawn(updater.run(watcher, closing));
+        Ok(Self { path: path.to_string(), state: receiver, _closer: closer })
+    }
+
+    /// Path of cached node.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Current data and stat of node, `None` if node does not exist.
+    pub fn current(&self) -> NodeState {
+        self.state.borrow().clone()
+    }
+
+    /// Waits until node changed and returns its latest state.
+    ///
+    /// Intermediate changes could be coalesced. This method will block indefinitely after cache
+    /// stopped updating.
+    pub async fn changed(&mut self) -> NodeState {
+        if self.state.changed().await.is_err() {
+            std::future::pending().await
+        }
+        self.state.borrow_and_update().clone()
+    }
+}
+
+struct CacheUpdater {
+    client: Client,
+    state_watcher: StateWatcher,
+    path: String,
+    sender: watch::Sender<NodeState>,
+}
+
+impl CacheUpdater {
+    fn update(&self, state: NodeState) {
+        if *self.sender.borrow() != state {
+            self.sender.send_replace(state);
+        }
+    }
+
+    /// Fetches node and returns watcher for its next change.
+    async fn fetch(&mut self) -> Result<OneshotWatcher> {
+        loop {
+            match self.client.get_and_watch_data(&self.path).await {
+                Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                Err(Error::NoNode) => match self.client.check_and_watch_stat(&self.path).await {
+                    Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                    Err(err) => return Err(err),
+                    // Created in between.
+                    Ok((Some(_), _)) => continue,
+                    Ok((None, watcher)) => {
+                        self.update(None);
+                        return Ok(watcher);
+                    },
+                },
+                Err(err) => return Err(err),
+                Ok((data, stat, watcher)) => {
+                    self.update(Some((data, stat)));
+                    return Ok(watcher);
+                },
+            }
+        }
+    }
+
+    /// Waits until node needs to be refetched.
+    async fn wait(&mut self, watcher: OneshotWatcher) -> Result<()> {
+        let changed = watcher.changed();
+        tokio::pin!(changed);
+        loop {
+            select! {
+                event = &mut changed => {
+                    if event.event_type == EventType::Session {
+                        self.state_watcher.wait_connected().await?;
+                    }
+                    return Ok(());
+                },
+                state = self.state_watcher.changed() => match state {
+                    SessionState::SyncConnected | SessionState::ConnectedReadOnly => {},
+                    _ => return self.state_watcher.wait_connected().await,
+                },
+            }
+        }
+    }
+
+    async fn run(mut self, mut watcher: OneshotWatcher, mut closing: oneshot::Receiver<()>) {
+        loop {
+            let result = select! {
+                _ = &mut closing => return,
+                r = async {
+                    self.wait(watcher).await?;
+                    self.fetch().await
+                } => r,
+            };
+            match result {
+                Ok(next) => watcher = next,
+                Err(err) => {
+                    log::warn!("ZooKeeper stops caching node {} due to {}", self.path, err);
+                    return;
+                },
+            }
+        }
+    }
+}
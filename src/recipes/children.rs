@@ -0,0 +1,180 @@
+use super::driver::{Action, CacheDriver, CacheSource, CachedNodes, Change, FetchFuture, NodeMap};
+use crate::client::{AddWatchMode, Client};
+use crate::error::Error;
+use crate::proto::Stat;
+use crate::session::{EventType, WatchedEvent};
+use crate::util;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Data and stat of child, `None` if data loading is skipped.
+type ChildState = Option<(Vec<u8>, Stat)>;
+
+/// Cached child of [PathChildrenCache].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChildData {
+    /// Path of child node.
+    pub path: String,
+
+    /// Data and stat of child node, `None` if data loading is skipped.
+    pub data: Option<(Vec<u8>, Stat)>,
+}
+
+/// Change of [PathChildrenCache].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathChildrenCacheEvent {
+    /// Child is added to cache.
+    Added(ChildData),
+
+    /// Child in cache is updated, it is never emitted if data loading is skipped.
+    Updated(ChildData),
+
+    /// Child is removed from cache, data is the last cached one.
+    Removed(ChildData),
+
+    /// Initial children have been added to cache.
+    Initialized,
+}
+
+/// Cache of direct children of a node, similar to `PathChildrenCache` in Apache Curator.
+///
+/// Children are listed and their data are loaded in batch with [crate::MultiReader], then kept
+/// current by persistent watch, see [Client::watch]. Data loading could be skipped for large
+/// groups, in which case only children paths are cached. Children are refetched after
+/// reconnection as persistent watch could lose events. Cache is not updated anymore after session
+/// terminated, say, expired without recreation.
+#[derive(Debug)]
+pub struct PathChildrenCache {
+    path: String,
+    cache: CachedNodes<ChildState, PathChildrenCacheEvent>,
+}
+
+impl PathChildrenCache {
+    /// Fetches children of given path, which could be absent, and keeps them current in
+    /// background until this instance dropped.
+    ///
+    /// Data of children are loaded and kept current only if `cache_data` is true.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `path` is not a valid path.
+    /// * [Error::NoAuth] if children are not readable.
+    pub async fn new(client: &Client, path: &str, cache_data: bool) -> Result<Self> {
+        util::validate_path(Default::default(), path, false)?;
+        let source = ChildrenSource {
+            path: path.to_string(),
+            tree: if path == "/" { path.to_string() } else { format!("{}/", path) },
+            cache_data,
+        };
+        let cache = CacheDriver::start(client, source).await?;
+        Ok(Self { path: path.to_string(), cache })
+    }
+
+    /// Path of parent node.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Cached child of given path.
+    pub fn get(&self, path: &str) -> Option<ChildData> {
+        let data = self.cache.nodes().get(path)?.clone();
+        Some(ChildData { path: path.to_string(), data })
+    }
+
+    /// All cached children in lexicographical order of their paths.
+    pub fn children(&self) -> Vec<ChildData> {
+        let children = self.cache.nodes();
+        children.iter().map(|(path, data)| ChildData { path: path.clone(), data: data.clone() }).collect()
+    }
+
+    /// Waits for next change of cache.
+    ///
+    /// Events are queued, so none of them are missed. This method will block indefinitely after
+    /// all events consumed and cache stopped updating.
+    pub async fn changed(&mut self) -> PathChildrenCacheEvent {
+        self.cache.changed().await
+    }
+
+    /// Stops queueing events for consumers which only read cached children.
+    #[cfg(feature = "discovery")]
+    pub(crate) fn close_events(&mut self) {
+        self.cache.close_events();
+    }
+}
+
+struct ChildrenSource {
+    path: String,
+    /// Parent path with trailing slash.
+    tree: String,
+    cache_data: bool,
+}
+
+impl ChildrenSource {
+    fn is_child(&self, path: &str) -> bool {
+        path != self.path && util::split_path(path).0 == self.path
+    }
+
+    async fn fetch_children(&self, client: &Client) -> Result<NodeMap<ChildState>> {
+        let names = match client.list_children(&self.path).await {
+            Err(Error::NoNode) => Vec::new(),
+            result => result?,
+        };
+        let paths: Vec<_> = names.into_iter().map(|name| format!("{}{}", self.tree, name)).collect();
+        if !self.cache_data {
+            return Ok(paths.into_iter().map(|path| (path, None)).collect());
+        }
+        let datas = super::get_data_batched(client, &paths).await?;
+        let children = paths.into_iter().zip(datas).filter(|(_, data)| data.is_some());
+        Ok(children.collect())
+    }
+}
+
+impl CacheSource for ChildrenSource {
+    type Event = PathChildrenCacheEvent;
+    type Value = ChildState;
+
+    const INITIALIZED: PathChildrenCacheEvent = PathChildrenCacheEvent::Initialized;
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn mode(&self) -> AddWatchMode {
+        // Recursive watch is required to observe data changes of children.
+        if self.cache_data {
+            AddWatchMode::PersistentRecursive
+        } else {
+            AddWatchMode::Persistent
+        }
+    }
+
+    fn fetch<'a>(&'a self, client: &'a Client) -> FetchFuture<'a, Self::Value> {
+        Box::pin(self.fetch_children(client))
+    }
+
+    fn action(&self, event: &WatchedEvent) -> Action {
+        match event.event_type {
+            // Persistent watch on parent when data loading is skipped.
+            EventType::NodeChildrenChanged => Action::Refresh,
+            EventType::NodeCreated | EventType::NodeDataChanged if self.is_child(&event.path) => Action::Fetch,
+            EventType::NodeDeleted if self.is_child(&event.path) => Action::Remove,
+            _ => Action::Skip,
+        }
+    }
+
+    fn value(data: Vec<u8>, stat: Stat) -> Self::Value {
+        Some((data, stat))
+    }
+
+    fn stat(value: &Self::Value) -> Option<&Stat> {
+        value.as_ref().map(|(_, stat)| stat)
+    }
+
+    fn event(change: Change, path: String, data: Self::Value) -> PathChildrenCacheEvent {
+        let child = ChildData { path, data };
+        match change {
+            Change::Added => PathChildrenCacheEvent::Added(child),
+            Change::Updated => PathChildrenCacheEvent::Updated(child),
+            Change::Removed => PathChildrenCacheEvent::Removed(child),
+        }
+    }
+}
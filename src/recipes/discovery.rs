@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{OwnedCreateOptions, OwnedRecipeOptions, PathChildrenCache, PersistentEphemeralNode, RecipeOptions};
+use crate::acl::Acls;
+use crate::client::{Client, CreateMode};
+use crate::error::Error;
+use crate::util;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Registration of dynamic instance, it is locked individually in registering so registrations of
+/// other instances are not blocked.
+type Registration = Arc<tokio::sync::Mutex<Option<PersistentEphemeralNode>>>;
+
+/// Type of [ServiceInstance], same as `ServiceType` in Apache Curator.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ServiceType {
+    /// Instance is registered as ephemeral node.
+    #[default]
+    Dynamic,
+
+    /// Instance is registered as ephemeral sequential node by Curator, it is not supported here.
+    DynamicSequential,
+
+    /// Instance is static and not registered.
+    Static,
+
+    /// Instance is registered as persistent node.
+    Permanent,
+}
+
+/// Part of [UriSpec].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UriSpecPart {
+    pub value: String,
+    pub variable: bool,
+}
+
+/// Template of service uri, same as `UriSpec` in Apache Curator.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UriSpec {
+    pub parts: Vec<UriSpecPart>,
+}
+
+/// Instance of service in JSON format of `ServiceInstance` in Apache Curator.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceInstance {
+    pub name: String,
+    pub id: String,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub ssl_port: Option<u16>,
+    #[serde(default)]
+    pub payload: Option<serde_json::Value>,
+    /// Milliseconds since unix epoch.
+    #[serde(default, rename = "registrationTimeUTC")]
+    pub registration_time_utc: i64,
+    #[serde(default)]
+    pub service_type: ServiceType,
+    #[serde(default)]
+    pub uri_spec: Option<UriSpec>,
+    #[serde(default = "ServiceInstance::default_enabled")]
+    pub enabled: bool,
+}
+
+impl ServiceInstance {
+    /// Constructs dynamic instance of given service with random id and current registration time.
+    pub fn new(name: &str) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self {
+            name: name.to_string(),
+            id: uuid::Uuid::new_v4().to_string(),
+            address: None,
+            port: None,
+            ssl_port: None,
+            payload: None,
+            registration_time_utc: now.as_millis() as i64,
+            service_type: ServiceType::Dynamic,
+            uri_spec: None,
+            enabled: true,
+        }
+    }
+
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data)
+            .map_err(|err| Error::UnexpectedError(format!("fail to parse service instance due to {}", err)))
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self)
+            .map_err(|err| Error::UnexpectedError(format!("fail to serialize service instance due to {}", err)))
+    }
+}
+
+/// Service registry compatible with `ServiceDiscovery` in Apache Curator x-discovery.
+///
+/// Instances are stored as JSON in nodes of path `{base_path}/{name}/{id}`. Dynamic instances are
+/// ephemeral nodes kept by [PersistentEphemeralNode], so they are re-registered after session
+/// reestablishment with [crate::ClientBuilder::with_session_recreation]. They are unregistered in
+/// background after this instance dropped.
+#[derive(Debug)]
+pub struct ServiceDiscovery {
+    client: Client,
+    base_path: String,
+    options: OwnedRecipeOptions,
+    services: Mutex<HashMap<(String, String), Registration>>,
+}
+
+impl ServiceDiscovery {
+    /// Constructs registry on given base path.
+    ///
+    /// [RecipeOptions] specifies acls for instance nodes and options to create base path and
+    /// service nodes if they are absent.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `base_path` is not a valid path.
+    /// * [Error::InvalidAcl] if acl is empty.
+    pub fn new<'a>(client: &Client, base_path: &str, options: impl Into<RecipeOptions<'a>>) -> Result<Self> {
+        util::validate_path(Default::default(), base_path, false)?;
+        let options = options.into();
+        if options.acls.is_empty() {
+            return Err(Error::InvalidAcl);
+        }
+        Ok(Self {
+            client: client.clone(),
+            base_path: base_path.to_string(),
+            options: OwnedRecipeOptions::new(&options),
+            services: Default::default(),
+        })
+    }
+
+    /// Base path of registry.
+    pub fn base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty() || name.contains('/') {
+            return Err(Error::BadArguments(&"service name and id must be non-empty node names"));
+        }
+        Ok(())
+    }
+
+    fn service_path(&self, name: &str) -> Result<String> {
+        Self::validate_name(name)?;
+        let path = if self.base_path == "/" { format!("/{}", name) } else { format!("{}/{}", self.base_path, name) };
+        Ok(path)
+    }
+
+    fn instance_path(&self, name: &str, id: &str) -> Result<String> {
+        Self::validate_name(id)?;
+        Ok(format!("{}/{}", self.service_path(name)?, id))
+    }
+
+    /// Creates service node and its ancestors.
+    async fn create_service(&self, service_path: &str) -> Result<()> {
+        let Some(options) = self.options.parent.as_ref().map(OwnedCreateOptions::to_options) else {
+            return Err(Error::NoNode);
+        };
+        self.client.create_ancestor_backword(service_path, &options).await
+    }
+
+    async fn register_permanent(&self, path: &str, data: &[u8]) -> Result<()> {
+        let options = CreateMode::Persistent.with_acls(Acls::new(&self.options.acls));
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.create(path, data, &options).await {
+                Ok(_) => return Ok(()),
+                Err(Error::NodeExists) => match self.client.set_data(path, data, None).await {
+                    Ok(_) => return Ok(()),
+                    Err(Error::NoNode) => continue,
+                    Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                    Err(err) => return Err(err),
+                },
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(Error::NoNode) => self.create_service(util::split_path(path).0).await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Deletes instance node of other session, say, an expired one, so it is not waited to be
+    /// deleted by server, as Apache Curator does.
+    async fn delete_stale(&self, path: &str) -> Result<()> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            let stat = match self.client.check_stat(path).await {
+                Err(Error::ConnectionLoss) => {
+                    state_watcher.wait_connected().await?;
+                    continue;
+                },
+                Err(err) => return Err(err),
+                Ok(None) => return Ok(()),
+                Ok(Some(stat)) => stat,
+            };
+            if stat.ephemeral_owner == self.client.session_id().0 {
+                return Ok(());
+            }
+            match self.client.delete(path, Some(stat.version)).await {
+                Ok(_) | Err(Error::NoNode) => return Ok(()),
+                Err(Error::BadVersion) => continue,
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Registers instance, or updates it if it has been registered by this registry.
+    ///
+    /// Dynamic instance is kept in background until unregistered, while permanent instance is
+    /// left in registry after this registry dropped. Existing node of other session is replaced.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if name or id is not a valid node name, or [ServiceType] is neither
+    ///   [ServiceType::Dynamic] nor [ServiceType::Permanent].
+    /// * [Error::NoNode] if service node does not exist and no ancestor options specified.
+    pub async fn register(&self, instance: &ServiceInstance) -> Result<()> {
+        let path = self.instance_path(&instance.name, &instance.id)?;
+        let data = instance.serialize()?;
+        match instance.service_type {
+            ServiceType::Dynamic => {},
+            ServiceType::Permanent => return self.register_permanent(&path, &data).await,
+            _ => return Err(Error::BadArguments(&"only dynamic and permanent instances could be registered")),
+        }
+        let key = (instance.name.clone(), instance.id.clone());
+        let registration = self.services.lock().unwrap().entry(key).or_default().clone();
+        let mut registration = registration.lock().await;
+        if let Some(node) = registration.as_ref() {
+            node.set_data(data);
+            return Ok(());
+        }
+        self.delete_stale(&path).await?;
+        let options = CreateMode::Ephemeral.with_acls(Acls::new(&self.options.acls));
+        let node = loop {
+            match PersistentEphemeralNode::new(&self.client, &path, &data, &options).await {
+                Err(Error::NoNode) => self.create_service(util::split_path(&path).0).await?,
+                result => break result?,
+            }
+        };
+        *registration = Some(node);
+        Ok(())
+    }
+
+    /// Unregisters instance of given service name and id, it is fine for the instance to be
+    /// absent already.
+    pub async fn unregister(&self, name: &str, id: &str) -> Result<()> {
+        let path = self.instance_path(name, id)?;
+        let registration = self.services.lock().unwrap().remove(&(name.to_string(), id.to_string()));
+        let node = match registration {
+            None => None,
+            Some(registration) => registration.lock().await.take(),
+        };
+        if let Some(node) = node {
+            return node.close().await;
+        }
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.delete(&path, None).await {
+                Ok(_) | Err(Error::NoNode) => return Ok(()),
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Queries names of all services.
+    pub async fn query_names(&self) -> Result<Vec<String>> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.list_children(&self.base_path).await {
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(Error::NoNode) => return Ok(Vec::new()),
+                result => return result,
+            }
+        }
+    }
+
+    /// Queries all instances of given service, unparsable instances are skipped.
+    pub async fn query_instances(&self, name: &str) -> Result<Vec<ServiceInstance>> {
+        let service_path = self.service_path(name)?;
+        let mut state_watcher = self.client.state_watcher();
+        let ids = loop {
+            match self.client.list_children(&service_path).await {
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(Error::NoNode) => return Ok(Vec::new()),
+                result => break result?,
+            }
+        };
+        let paths: Vec<_> = ids.into_iter().map(|id| format!("{}/{}", service_path, id)).collect();
+        let datas = loop {
+            match super::get_data_batched(&self.client, &paths).await {
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                result => break result?,
+            }
+        };
+        let mut instances = Vec::with_capacity(paths.len());
+        for (path, data) in paths.iter().zip(datas) {
+            let Some((data, _stat)) = data else {
+                continue;
+            };
+            match ServiceInstance::parse(&data) {
+                Ok(instance) => instances.push(instance),
+                Err(err) => log::warn!("ZooKeeper skips service instance {} due to {}", path, err),
+            }
+        }
+        Ok(instances)
+    }
+
+    /// Queries instance of given service name and id.
+    ///
+    /// # Notable errors
+    /// * [Error::UnexpectedError] if instance is not parsable.
+    pub async fn query_instance(&self, name: &str, id: &str) -> Result<Option<ServiceInstance>> {
+        let path = self.instance_path(name, id)?;
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.get_data(&path).await {
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(Error::NoNode) => return Ok(None),
+                Err(err) => return Err(err),
+                Ok((data, _stat)) => return ServiceInstance::parse(&data).map(Some),
+            }
+        }
+    }
+
+    /// Constructs provider for instances of given service after initial instances cached.
+    ///
+    /// # Notable errors
+    /// * [Error::NoAuth] if instances are not readable.
+    pub async fn provider(&self, name: &str, strategy: ProviderStrategy) -> Result<ServiceProvider> {
+        let mut cache = PathChildrenCache::new(&self.client, &self.service_path(name)?, true).await?;
+        // Nobody consumes events afterwards.
+        cache.close_events();
+        Ok(ServiceProvider { cache, strategy, index: AtomicUsize::new(0) })
+    }
+}
+
+/// Strategy for [ServiceProvider] to select instance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProviderStrategy {
+    RoundRobin,
+    Random,
+}
+
+/// Provider of instances of one service, which are cached and kept current in background.
+#[derive(Debug)]
+pub struct ServiceProvider {
+    cache: PathChildrenCache,
+    strategy: ProviderStrategy,
+    index: AtomicUsize,
+}
+
+impl ServiceProvider {
+    /// All cached instances, unparsable instances are skipped.
+    pub fn instances(&self) -> Vec<ServiceInstance> {
+        let children = self.cache.children();
+        let datas = children.iter().filter_map(|child| Some((child.path.as_str(), &child.data.as_ref()?.0)));
+        datas
+            .filter_map(|(path, data)| match ServiceInstance::parse(data) {
+                Ok(instance) => Some(instance),
+                Err(err) => {
+                    log::warn!("ZooKeeper skips service instance {} due to {}", path, err);
+                    None
+                },
+            })
+            .collect()
+    }
+
+    /// Selects one of enabled instances using [ProviderStrategy], `None` if there is none.
+    pub fn instance(&self) -> Option<ServiceInstance> {
+        let mut instances = self.instances();
+        instances.retain(|instance| instance.enabled);
+        if instances.is_empty() {
+            return None;
+        }
+        let i = match self.strategy {
+            ProviderStrategy::RoundRobin => self.index.fetch_add(1, Ordering::Relaxed) % instances.len(),
+            ProviderStrategy::Random => rand::thread_rng().gen_range(0..instances.len()),
+        };
+        Some(instances.swap_remove(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_curator_format() {
+        let json = br#"{"name":"test","id":"d1ff2a1c-6a9e-4c6e-9f2a-3c1c8b2c4d11","address":"10.0.0.1","port":1234,"sslPort":null,"payload":null,"registrationTimeUTC":1700000000000,"serviceType":"DYNAMIC","uriSpec":{"parts":[{"value":"scheme","variable":true},{"value":"://","variable":false}]}}"#;
+        let instance = ServiceInstance::parse(json).unwrap();
+        assert_eq!(instance.name, "test");
+        assert_eq!(instance.address.as_deref(), Some("10.0.0.1"));
+        assert_eq!(instance.port, Some(1234));
+        assert_eq!(instance.ssl_port, None);
+        assert_eq!(instance.registration_time_utc, 1700000000000);
+        assert_eq!(instance.service_type, ServiceType::Dynamic);
+        assert_eq!(instance.uri_spec.as_ref().unwrap().parts[0], UriSpecPart {
+            value: "scheme".to_string(),
+            variable: true
+        });
+        assert!(instance.enabled);
+
+        let value: serde_json::Value = serde_json::from_slice(&instance.serialize().unwrap()).unwrap();
+        assert_eq!(value["registrationTimeUTC"], 1700000000000i64);
+        assert_eq!(value["serviceType"], "DYNAMIC");
+        assert_eq!(value["sslPort"], serde_json::Value::Null);
+        assert_eq!(ServiceInstance::parse(&instance.serialize().unwrap()).unwrap(), instance);
+    }
+}
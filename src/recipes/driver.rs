@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use ignore_result::Ignore;
+use tokio::select;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::client::{AddWatchMode, Client, PersistentWatcher, StateWatcher};
+use crate::error::Error;
+use crate::proto::Stat;
+use crate::session::{EventType, SessionState, WatchedEvent};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Cached nodes by their paths.
+pub(super) type NodeMap<T> = BTreeMap<String, T>;
+
+/// Future of [CacheSource::fetch].
+pub(super) type FetchFuture<'a, T> = Pin<Box<dyn Future<Output = Result<NodeMap<T>>> + Send + 'a>>;
+
+/// Change of cached node.
+pub(super) enum Change {
+    Added,
+    Updated,
+    Removed,
+}
+
+/// Action to apply node event of persistent watch.
+pub(super) enum Action {
+    /// Refetches all nodes.
+    Refresh,
+
+    /// Refetches node of event.
+    Fetch,
+
+    /// Removes node of event.
+    Remove,
+
+    /// Skips event.
+    Skip,
+}
+
+/// Cache specific fetching and event mapping for [CacheDriver].
+pub(super) trait CacheSource: Send + 'static {
+    /// Cached value of node.
+    type Value: Clone + Send + 'static;
+
+    /// Change event of cache.
+    type Event: Send + 'static;
+
+    /// Event sent after initial nodes fetched.
+    const INITIALIZED: Self::Event;
+
+    /// Path of persistent watch.
+    fn path(&self) -> &str;
+
+    /// Mode of persistent watch.
+    fn mode(&self) -> AddWatchMode;
+
+    /// Fetches all nodes, absent path results in no nodes.
+    fn fetch<'a>(&'a self, client: &'a Client) -> FetchFuture<'a, Self::Value>;
+
+    /// Maps node event of persistent watch to action.
+    fn action(&self, event: &WatchedEvent) -> Action;
+
+    /// Constructs cached value from refetched node.
+    fn value(data: Vec<u8>, stat: Stat) -> Self::Value;
+
+    /// Stat of cached value, `None` if it is not cached.
+    fn stat(value: &Self::Value) -> Option<&Stat>;
+
+    /// Constructs event for change of node.
+    fn event(change: Change, path: String, value: Self::Value) -> Self::Event;
+}
+
+/// Nodes cached by [CacheDriver], they are not updated anymore after this dropped.
+#[derive(Debug)]
+pub(super) struct CachedNodes<T, E> {
+    nodes: Arc<Mutex<NodeMap<T>>>,
+    events: mpsc::UnboundedReceiver<E>,
+    _closer: oneshot::Sender<()>,
+}
+
+impl<T, E> CachedNodes<T, E> {
+    pub fn nodes(&self) -> MutexGuard<'_, NodeMap<T>> {
+        self.nodes.lock().unwrap()
+    }
+
+    pub async fn changed(&mut self) -> E {
+        match self.events.recv().await {
+            None => std::future::pending().await,
+            Some(event) => event,
+        }
+    }
+
+    /// Stops queueing events for consumers which only read cached nodes.
+    #[cfg(feature = "discovery")]
+    pub fn close_events(&mut self) {
+        self.events.close();
+    }
+}
+
+/// Keeps nodes current with persistent watch, nodes are refetched after reconnection as
+/// persistent watch could lose events, see [ZOOKEEPER-4698][].
+///
+/// [ZOOKEEPER-4698]: https://issues.apache.org/jira/browse/ZOOKEEPER-4698
+pub(super) struct CacheDriver<S: CacheSource> {
+    client: Client,
+    state_watcher: StateWatcher,
+    source: S,
+    nodes: Arc<Mutex<NodeMap<S::Value>>>,
+    events: mpsc::UnboundedSender<S::Event>,
+}
+
+impl<S: CacheSource> CacheDriver<S> {
+    /// Fetches initial nodes and keeps them current in background.
+    pub async fn start(client: &Client, source: S) -> Result<CachedNodes<S::Value, S::Event>> {
+        let nodes = Arc::new(Mutex::new(NodeMap::new()));
+        let (events_sender, events) = mpsc::unbounded_channel();
+        let mut driver = Self {
+            client: client.clone(),
+            state_watcher: client.state_watcher(),
+            source,
+            nodes: nodes.clone(),
+            events: events_sender,
+        };
+        let watcher = driver.watch().await?;
+        driver.refresh().await?;
+        driver.send(S::INITIALIZED);
+        let (closer, closing) = oneshot::channel();
+        tokio::spawn(driver.run(watcher, closing));
+        Ok(CachedNodes { nodes, events, _closer: closer })
+    }
+
+    fn send(&self, event: S::Event) {
+        self.events.send(event).ignore();
+    }
+
+    fn is_same(cached: &S::Value, fetched: &S::Value) -> bool {
+        match (S::stat(cached), S::stat(fetched)) {
+            (Some(cached), Some(fetched)) => cached.mzxid == fetched.mzxid && cached.czxid == fetched.czxid,
+            _ => true,
+        }
+    }
+
+    /// Refetches all nodes and sends differences as events.
+    async fn refresh(&mut self) -> Result<()> {
+        let mut fetched = loop {
+            match self.source.fetch(&self.client).await {
+                Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                result => break result?,
+            }
+        };
+        let mut events = Vec::new();
+        {
+            let mut nodes = self.nodes.lock().unwrap();
+            for (path, value) in nodes.iter() {
+                if !fetched.contains_key(path) {
+                    events.push(S::event(Change::Removed, path.clone(), value.clone()));
+                }
+            }
+            for (path, value) in fetched.iter() {
+                match nodes.get(path) {
+                    None => events.push(S::event(Change::Added, path.clone(), value.clone())),
+                    Some(cached) if !Self::is_same(cached, value) => {
+                        events.push(S::event(Change::Updated, path.clone(), value.clone()))
+                    },
+                    _ => {},
+                }
+            }
+            std::mem::swap(&mut *nodes, &mut fetched);
+        }
+        events.into_iter().for_each(|event| self.send(event));
+        Ok(())
+    }
+
+    /// Refetches one node and sends its change as event.
+    async fn refresh_node(&mut self, path: &str) -> Result<()> {
+        let fetched = match self.client.get_data(path).await {
+            Err(Error::NoNode) => {
+                self.remove_node(path);
+                return Ok(());
+            },
+            Err(err) => return Err(err),
+            Ok((data, stat)) => S::value(data, stat),
+        };
+        let event = {
+            let mut nodes = self.nodes.lock().unwrap();
+            let change = match nodes.get(path) {
+                None => Change::Added,
+                Some(cached) if Self::is_same(cached, &fetched) => return Ok(()),
+                Some(_) => Change::Updated,
+            };
+            nodes.insert(path.to_string(), fetched.clone());
+            S::event(change, path.to_string(), fetched)
+        };
+        self.send(event);
+        Ok(())
+    }
+
+    fn remove_node(&self, path: &str) {
+        let Some(value) = self.nodes.lock().unwrap().remove(path) else {
+            return;
+        };
+        self.send(S::event(Change::Removed, path.to_string(), value));
+    }
+
+    /// Applies events from watcher until it is broken by session expiration.
+    async fn follow(&mut self, watcher: &mut PersistentWatcher) -> Result<()> {
+        let mut resync = false;
+        loop {
+            let event = watcher.changed().await;
+            let result = match event.event_type {
+                EventType::Session => match event.session_state {
+                    SessionState::Disconnected => {
+                        resync = true;
+                        continue;
+                    },
+                    SessionState::SyncConnected | SessionState::ConnectedReadOnly if resync => {
+                        resync = false;
+                        self.refresh().await
+                    },
+                    state if state.is_terminated() => return Ok(()),
+                    _ => continue,
+                },
+                _ => match self.source.action(&event) {
+                    Action::Refresh => self.refresh().await,
+                    Action::Fetch => self.refresh_node(&event.path).await,
+                    Action::Remove => {
+                        self.remove_node(&event.path);
+                        continue;
+                    },
+                    Action::Skip => continue,
+                },
+            };
+            match result {
+                Err(Error::ConnectionLoss) => resync = true,
+                Err(err) => return Err(err),
+                Ok(_) => {},
+            }
+        }
+    }
+
+    async fn watch(&mut self) -> Result<PersistentWatcher> {
+        loop {
+            match self.client.watch(self.source.path(), self.source.mode()).await {
+                Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                result => return result,
+            }
+        }
+    }
+
+    async fn serve(&mut self, mut watcher: PersistentWatcher) -> Result<()> {
+        loop {
+            self.follow(&mut watcher).await?;
+            // Session expired, waits for recreation.
+            self.state_watcher.wait_connected().await?;
+            watcher = self.watch().await?;
+            self.refresh().await?;
+        }
+    }
+
+    async fn run(mut self, watcher: PersistentWatcher, mut closing: oneshot::Receiver<()>) {
+        select! {
+            _ = &mut closing => {},
+            r = self.serve(watcher) => if let Err(err) = r {
+                log::warn!("ZooKeeper stops caching nodes of {} due to {}", self.source.path(), err);
+            },
+        }
+    }
+}
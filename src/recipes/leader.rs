@@ -0,0 +1,282 @@
+use ignore_result::Ignore;
+use tokio::select;
+use tokio::sync::{mpsc, oneshot, watch};
+
+use super::OwnedLockOptions;
+use crate::client::{Client, LockOptions, LockPrefix, MultiReadResult, OwnedLockClient, StateWatcher};
+use crate::error::Error;
+use crate::session::{EventType, SessionState};
+
+type Result<T> = std::result::Result<T, Error>;
+
+const LATCH_NAME: &str = "latch-";
+const SEQUENCE_LEN: usize = 10;
+
+/// Sequence suffix of child, `None` if it is too short or not on char boundary.
+fn sequence_of(child: &str) -> Option<&str> {
+    child.get(child.len().checked_sub(SEQUENCE_LEN)?..)
+}
+
+/// Leadership change of [LeaderLatch].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LeaderEvent {
+    /// This latch becomes leader.
+    Gained,
+
+    /// This latch is not leader anymore.
+    Lost,
+}
+
+/// Leader election compatible with `LeaderLatch` in Apache Curator.
+///
+/// Participants contend through [Client::lock] using [LockPrefix::new_curator] with lock name
+/// `latch-` under given directory, and participant id is stored as data of lock path. The
+/// participant owning the first lock path is leader.
+///
+/// Leadership is considered lost after session disconnected as other participants could take over
+/// it after session expired. It is regained if lock path survives reconnection, otherwise this
+/// latch contends again. Together with [crate::ClientBuilder::with_session_recreation], this latch
+/// survives session expiration.
+///
+/// Lock path is deleted in background after this instance dropped, see [LeaderLatch::close] to
+/// delete it in foreground.
+#[derive(Debug)]
+pub struct LeaderLatch {
+    client: Client,
+    dir: String,
+    id: String,
+    leader: watch::Receiver<bool>,
+    events: mpsc::UnboundedReceiver<LeaderEvent>,
+    closer: oneshot::Sender<oneshot::Sender<Result<()>>>,
+}
+
+impl LeaderLatch {
+    /// Starts contending leadership under given directory with given participant id.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `dir` is not a valid path.
+    /// * [Error::InvalidAcl] if acl is empty.
+    ///
+    /// # Notable behaviors
+    /// Errors in contention, say [Error::NoNode] for absent directory, stop this latch. Use
+    /// [LockOptions::with_ancestor_options] to create absent directory.
+    pub fn new<'a>(client: &Client, dir: &str, id: &str, options: impl Into<LockOptions<'a>>) -> Result<Self> {
+        LockPrefix::new_curator(dir, LATCH_NAME)?;
+        let options = options.into();
+        if options.acls.is_empty() {
+            return Err(Error::InvalidAcl);
+        }
+        let (leader_sender, leader_receiver) = watch::channel(false);
+        let (events_sender, events_receiver) = mpsc::unbounded_channel();
+        let contender = LatchContender {
+            client: client.clone(),
+            state_watcher: client.state_watcher(),
+            dir: dir.to_string(),
+            id: id.to_string(),
+            options: OwnedLockOptions::new(&options),
+            leader: leader_sender,
+            events: events_sender,
+            lock: None,
+        };
+        let (closer, closing) = oneshot::channel();
+        tokio::spawn(contender.run(closing));
+        Ok(Self {
+            client: client.clone(),
+            dir: dir.to_string(),
+            id: id.to_string(),
+            leader: leader_receiver,
+            events: events_receiver,
+            closer,
+        })
+    }
+
+    /// Participant id of this latch.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns whether this latch is leader currently.
+    pub fn is_leader(&self) -> bool {
+        *self.leader.borrow()
+    }
+
+    /// Waits for next leadership change.
+    ///
+    /// Events are queued, so none of them are missed. This method will block indefinitely after
+    /// all events consumed and this latch stopped contending.
+    pub async fn changed(&mut self) -> LeaderEvent {
+        match self.events.recv().await {
+            None => std::future::pending().await,
+            Some(event) => event,
+        }
+    }
+
+    /// Lists ids of participants in contending order, so leader comes first.
+    ///
+    /// Participants departed during listing are skipped.
+    pub async fn participants(&self) -> Result<Vec<String>> {
+        let mut children = match self.client.list_children(&self.dir).await {
+            Err(Error::NoNode) => return Ok(Vec::new()),
+            result => result?,
+        };
+        children.retain(|child| {
+            child.contains(LATCH_NAME) && sequence_of(child).map_or(false, |sequence| sequence.parse::<i32>().is_ok())
+        });
+        if children.is_empty() {
+            return Ok(Vec::new());
+        }
+        children.sort_unstable_by(|a, b| sequence_of(a).cmp(&sequence_of(b)));
+        let mut reader = self.client.new_multi_reader();
+        for child in children.iter() {
+            reader.add_get_data(&format!("{}/{}", self.dir, child))?;
+        }
+        let results = reader.commit().await?;
+        let ids = results
+            .into_iter()
+            .filter_map(|result| match result {
+                MultiReadResult::Data { data, .. } => Some(String::from_utf8_lossy(&data).into_owned()),
+                _ => None,
+            })
+            .collect();
+        Ok(ids)
+    }
+
+    /// Reads id of current leader from data of the first lock path, `None` if there is no
+    /// participant.
+    pub async fn leader_id(&self) -> Result<Option<String>> {
+        let mut participants = self.participants().await?;
+        if participants.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(participants.swap_remove(0)))
+    }
+
+    /// Stops contending and deletes lock path.
+    pub async fn close(self) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.closer.send(sender).ignore();
+        receiver.await.unwrap_or(Ok(()))
+    }
+}
+
+struct LatchContender {
+    client: Client,
+    state_watcher: StateWatcher,
+    dir: String,
+    id: String,
+    options: OwnedLockOptions,
+    leader: watch::Sender<bool>,
+    events: mpsc::UnboundedSender<LeaderEvent>,
+    lock: Option<OwnedLockClient>,
+}
+
+impl LatchContender {
+    fn set_leader(&mut self, leader: bool) {
+        if *self.leader.borrow() == leader {
+            return;
+        }
+        self.leader.send_replace(leader);
+        let event = if leader { LeaderEvent::Gained } else { LeaderEvent::Lost };
+        self.events.send(event).ignore();
+    }
+
+    /// Contends until lock acquired, recontends if lock path lost in contention.
+    async fn contend(&mut self) -> Result<OwnedLockClient> {
+        loop {
+            let prefix = LockPrefix::new_curator(&self.dir, LATCH_NAME)?;
+            match self.client.lock(prefix, self.id.as_bytes(), self.options.to_options()).await {
+                Ok(lock) => return Ok(lock.into_owned()),
+                Err(Error::SessionExpired | Error::RuntimeInconsistent) => self.state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Holds leadership until lock path lost.
+    async fn hold(&mut self, path: &str) -> Result<()> {
+        loop {
+            let watcher = match self.client.check_and_watch_stat(path).await {
+                Err(Error::ConnectionLoss) => {
+                    self.set_leader(false);
+                    self.state_watcher.wait_connected().await?;
+                    continue;
+                },
+                Err(err) => return Err(err),
+                Ok((Some(stat), watcher)) if stat.ephemeral_owner == self.client.session_id().0 => watcher,
+                Ok(_) => return Ok(()),
+            };
+            self.set_leader(true);
+            select! {
+                event = watcher.changed() => match event.event_type {
+                    EventType::NodeDeleted | EventType::Session => return Ok(()),
+                    _ => {},
+                },
+                state = self.state_watcher.changed() => match state {
+                    SessionState::SyncConnected | SessionState::ConnectedReadOnly => {},
+                    SessionState::Disconnected => {
+                        self.set_leader(false);
+                        self.state_watcher.wait_connected().await?;
+                    },
+                    _ => return Ok(()),
+                },
+            }
+        }
+    }
+
+    async fn serve(&mut self) -> Result<()> {
+        loop {
+            let lock = self.contend().await?;
+            let path = lock.lock_path().to_string();
+            self.lock = Some(lock);
+            self.hold(&path).await?;
+            self.set_leader(false);
+            self.lock = None;
+        }
+    }
+
+    async fn delete(&mut self, path: &str) -> Result<()> {
+        loop {
+            match self.client.delete(path, None).await {
+                Ok(_) | Err(Error::NoNode) => return Ok(()),
+                Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn run(mut self, mut closing: oneshot::Receiver<oneshot::Sender<Result<()>>>) {
+        let responser = select! {
+            biased;
+            r = &mut closing => r.ok(),
+            r = self.serve() => {
+                if let Err(err) = r {
+                    log::warn!("ZooKeeper leader latch {} in {} stops due to {}", self.id, self.dir, err);
+                }
+                self.set_leader(false);
+                return;
+            },
+        };
+        self.set_leader(false);
+        let result = match self.lock.take() {
+            None => Ok(()),
+            Some(lock) => self.delete(lock.lock_path()).await,
+        };
+        if let Some(responser) = responser {
+            responser.send(result).ignore();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_sequence_of() {
+        assert_eq!(sequence_of("_c_uuid-latch-0000000010"), Some("0000000010"));
+        assert_eq!(sequence_of("latch-01"), None);
+        assert_eq!(sequence_of("latch-é000000000"), None);
+    }
+}
@@ -0,0 +1,153 @@
+mod atomic;
+mod barrier;
+mod cache;
+mod children;
+#[cfg(feature = "discovery")]
+mod discovery;
+mod driver;
+mod leader;
+mod mutex;
+mod node;
+mod queue;
+mod semaphore;
+mod tree;
+
+use std::time::Duration;
+
+pub use self::atomic::{AtomicValue, DistributedAtomicLong};
+pub use self::barrier::{Barrier, DoubleBarrier, DoubleBarrierMember};
+pub use self::cache::NodeCache;
+pub use self::children::{ChildData, PathChildrenCache, PathChildrenCacheEvent};
+#[cfg(feature = "discovery")]
+pub use self::discovery::{
+    ProviderStrategy,
+    ServiceDiscovery,
+    ServiceInstance,
+    ServiceProvider,
+    ServiceType,
+    UriSpec,
+    UriSpecPart,
+};
+pub use self::leader::{LeaderEvent, LeaderLatch};
+pub use self::mutex::{InterProcessMutex, InterProcessMutexGuard};
+pub use self::node::PersistentEphemeralNode;
+pub use self::queue::{DistributedPriorityQueue, DistributedQueue};
+pub use self::semaphore::{InterProcessSemaphore, Lease};
+pub use self::tree::{TreeCache, TreeCacheEvent};
+use crate::acl::{Acl, Acls};
+use crate::client::{Client, CreateMode, CreateOptions, LockOptions, MultiReadResult};
+use crate::error::Error;
+use crate::proto::Stat;
+
+/// Maximum number of operations in one multi read.
+const BATCH_SIZE: usize = 128;
+
+/// Gets data of given paths in batches of [crate::MultiReader], `None` for absent nodes.
+async fn get_data_batched(client: &Client, paths: &[String]) -> Result<Vec<Option<(Vec<u8>, Stat)>>, Error> {
+    let mut datas = Vec::with_capacity(paths.len());
+    for paths in paths.chunks(BATCH_SIZE) {
+        let mut reader = client.new_multi_reader();
+        for path in paths {
+            reader.add_get_data(path)?;
+        }
+        for result in reader.commit().await? {
+            match result {
+                MultiReadResult::Data { data, stat } => datas.push(Some((data, stat))),
+                MultiReadResult::Error { err: Error::NoNode } => datas.push(None),
+                MultiReadResult::Error { err } => return Err(err),
+                MultiReadResult::Children { .. } => {
+                    return Err(Error::UnexpectedError("got children for get data in multi read".to_string()))
+                },
+            }
+        }
+    }
+    Ok(datas)
+}
+
+/// Owned version of [CreateOptions] for background tasks.
+#[derive(Clone, Debug)]
+struct OwnedCreateOptions {
+    mode: CreateMode,
+    acls: Vec<Acl>,
+    ttl: Option<Duration>,
+}
+
+impl OwnedCreateOptions {
+    fn new(options: &CreateOptions<'_>) -> Self {
+        Self { mode: options.mode, acls: options.acls.to_vec(), ttl: options.ttl }
+    }
+
+    fn to_options(&self) -> CreateOptions<'_> {
+        CreateOptions { mode: self.mode, acls: Acls::new(&self.acls), ttl: self.ttl }
+    }
+}
+
+/// Options to cover [Acls] for nodes created by recipe and [CreateOptions] for ancestor nodes if
+/// they don't exist.
+#[derive(Clone, Debug)]
+pub struct RecipeOptions<'a> {
+    acls: Acls<'a>,
+    parent: Option<CreateOptions<'a>>,
+}
+
+impl<'a> RecipeOptions<'a> {
+    pub fn new(acls: Acls<'a>) -> Self {
+        Self { acls, parent: None }
+    }
+
+    /// Creates ancestor nodes if not exist using given options.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if [CreateMode] is ephemeral or sequential.
+    /// * [Error::InvalidAcl] if acl is invalid or empty.
+    pub fn with_ancestor_options(mut self, options: CreateOptions<'a>) -> Result<Self, Error> {
+        options.validate()?;
+        if options.mode.is_ephemeral() {
+            return Err(Error::BadArguments(&"ancestor node must not be ephemeral"));
+        } else if options.mode.is_sequential() {
+            return Err(Error::BadArguments(&"ancestor node must not be sequential"));
+        }
+        self.parent = Some(options);
+        Ok(self)
+    }
+}
+
+impl<'a> From<Acls<'a>> for RecipeOptions<'a> {
+    fn from(acls: Acls<'a>) -> Self {
+        RecipeOptions::new(acls)
+    }
+}
+
+/// Owned version of [RecipeOptions] for background tasks.
+#[derive(Clone, Debug)]
+struct OwnedRecipeOptions {
+    acls: Vec<Acl>,
+    parent: Option<OwnedCreateOptions>,
+}
+
+impl OwnedRecipeOptions {
+    fn new(options: &RecipeOptions<'_>) -> Self {
+        Self { acls: options.acls.to_vec(), parent: options.parent.as_ref().map(OwnedCreateOptions::new) }
+    }
+
+    fn to_lock_options(&self) -> LockOptions<'_> {
+        LockOptions { acls: Acls::new(&self.acls), parent: self.parent.as_ref().map(|options| options.to_options()) }
+    }
+}
+
+/// Owned version of [LockOptions] for background tasks.
+#[derive(Clone, Debug)]
+struct OwnedLockOptions {
+    acls: Vec<Acl>,
+    parent: Option<OwnedCreateOptions>,
+}
+
+impl OwnedLockOptions {
+    fn new(options: &LockOptions<'_>) -> Self {
+        Self { acls: options.acls.to_vec(), parent: options.parent.as_ref().map(OwnedCreateOptions::new) }
+    }
+
+    fn to_options(&self) -> LockOptions<'_> {
+        LockOptions { acls: Acls::new(&self.acls), parent: self.parent.as_ref().map(|options| options.to_options()) }
+    }
+}
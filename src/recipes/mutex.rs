@@ -0,0 +1,90 @@
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+
+use super::OwnedLockOptions;
+use crate::client::{Client, LockOptions, LockPrefix, OwnedLockClient};
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const LOCK_NAME: &str = "lock-";
+
+/// Reentrant lock compatible with `InterProcessMutex` in Apache Curator.
+///
+/// This instance is the holder of lock, so it creates at most one lock path no matter how many
+/// times [InterProcessMutex::acquire] is called. Lock path is deleted in background after all
+/// guards dropped. [InterProcessMutex::acquire] takes `&mut self`, so reentrance is restricted
+/// to the owner of this instance, and tasks which need mutual exclusion among themselves should
+/// use separate instances.
+#[derive(Debug)]
+pub struct InterProcessMutex {
+    client: Client,
+    dir: String,
+    options: OwnedLockOptions,
+    holding: Weak<OwnedLockClient>,
+}
+
+/// Guard of [InterProcessMutex], the last dropped guard releases the lock.
+///
+/// Cloning a guard reenters the lock.
+#[derive(Clone, Debug)]
+pub struct InterProcessMutexGuard {
+    lock: Arc<OwnedLockClient>,
+}
+
+impl Deref for InterProcessMutexGuard {
+    type Target = OwnedLockClient;
+
+    fn deref(&self) -> &OwnedLockClient {
+        &self.lock
+    }
+}
+
+impl InterProcessMutex {
+    /// Constructs lock under given directory.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `dir` is not a valid path.
+    /// * [Error::InvalidAcl] if acl is empty.
+    pub fn new<'a>(client: &Client, dir: &str, options: impl Into<LockOptions<'a>>) -> Result<Self> {
+        LockPrefix::new_curator(dir, LOCK_NAME)?;
+        let options = options.into();
+        if options.acls.is_empty() {
+            return Err(Error::InvalidAcl);
+        }
+        Ok(Self {
+            client: client.clone(),
+            dir: dir.to_string(),
+            options: OwnedLockOptions::new(&options),
+            holding: Weak::new(),
+        })
+    }
+
+    /// Number of live guards, zero if lock is not held by this instance.
+    pub fn hold_count(&self) -> usize {
+        self.holding.strong_count()
+    }
+
+    /// Returns whether lock is held by this instance.
+    pub fn is_held(&self) -> bool {
+        self.hold_count() != 0
+    }
+
+    /// Acquires lock, or reenters it if it is held by this instance already.
+    ///
+    /// # Notable errors
+    /// Same as [Client::lock].
+    ///
+    /// # Cancellation safety
+    /// This method is cancellation safe as [Client::lock] is.
+    pub async fn acquire(&mut self) -> Result<InterProcessMutexGuard> {
+        if let Some(lock) = self.holding.upgrade() {
+            return Ok(InterProcessMutexGuard { lock });
+        }
+        let prefix = LockPrefix::new_curator(&self.dir, LOCK_NAME)?;
+        let lock = self.client.lock(prefix, Default::default(), self.options.to_options()).await?;
+        let lock = Arc::new(lock.into_owned());
+        self.holding = Arc::downgrade(&lock);
+        Ok(InterProcessMutexGuard { lock })
+    }
+}
@@ -0,0 +1,310 @@
+use ignore_result::Ignore;
+use tokio::select;
+use tokio::sync::{oneshot, watch};
+use tokio::time::{self, Instant, Interval};
+
+use super::OwnedCreateOptions;
+use crate::client::{Client, CreateMode, CreateOptions, CreateSequence, StateWatcher};
+use crate::error::Error;
+use crate::proto::Stat;
+use crate::session::EventType;
+use crate::util;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Node that is kept present with desired data by re-creating it after deletion or session
+/// reestablishment, similar to `PersistentNode` in Apache Curator.
+///
+/// It is mostly used for ephemeral node, e.g. service registration, together with
+/// [crate::ClientBuilder::with_session_recreation] so node survives session expiration.
+/// Persistent node with ttl is refreshed every half of ttl, so it is deleted by server after
+/// this instance gone.
+///
+/// Node is deleted in background after this instance dropped, see
+/// [PersistentEphemeralNode::close] to delete it in foreground.
+#[derive(Debug)]
+pub struct PersistentEphemeralNode {
+    path: watch::Receiver<Option<String>>,
+    data: watch::Sender<Vec<u8>>,
+    closer: oneshot::Sender<oneshot::Sender<Result<()>>>,
+}
+
+impl PersistentEphemeralNode {
+    /// Creates node with given path, data and options, and keeps it present in background.
+    ///
+    /// Sequential node gets a new path in re-creation. Ephemeral sequential node is created with
+    /// path `{dir}/_c_{uuid}-{name}{sequence}` as protected mode of Apache Curator does, so it
+    /// could be found after connection loss without confusion with other nodes. Existing node is
+    /// taken over with data overwritten unless it is an ephemeral node of other session, in which
+    /// case it is waited to be deleted.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if mode is [CreateMode::Container].
+    /// * [Error::NoNode] if parent node does not exist.
+    /// * Other errors from [Client::create].
+    ///
+    /// # Notable behaviors
+    /// * Node is not kept anymore after session terminated, say, expired without recreation.
+    /// * Persistent sequential node could leak due to connection loss or closing in creation.
+    pub async fn new(client: &Client, path: &str, data: &[u8], options: &CreateOptions<'_>) -> Result<Self> {
+        options.validate()?;
+        if options.mode == CreateMode::Container {
+            return Err(Error::BadArguments(&"container node could not be kept"));
+        }
+        let (data_sender, data_receiver) = watch::channel(data.to_vec());
+        let (path_sender, path_receiver) = watch::channel(None);
+        let mut keeper = NodeKeeper {
+            client: client.clone(),
+            state_watcher: client.state_watcher(),
+            prefix: Self::protect(path, options.mode),
+            options: OwnedCreateOptions::new(options),
+            data: data_receiver,
+            path: path_sender,
+            current: None,
+            outdated: false,
+        };
+        let path = keeper.create().await?;
+        keeper.set_path(Some(path));
+        let (closer, closing) = oneshot::channel();
+        tokio::spawn(keeper.keep(closing));
+        Ok(Self { path: path_receiver, data: data_sender, closer })
+    }
+
+    fn protect(path: &str, mode: CreateMode) -> String {
+        if !mode.is_sequential() || !mode.is_ephemeral() {
+            return path.to_string();
+        }
+        let (_, tree, name) = util::split_path(path);
+        format!("{}_c_{}-{}", tree, uuid::Uuid::new_v4(), name)
+    }
+
+    /// Current path of node, `None` if it is absent for re-creation or not kept anymore.
+    pub fn path(&self) -> Option<String> {
+        self.path.borrow().clone()
+    }
+
+    /// Waits until path changed and returns it.
+    ///
+    /// This method will block indefinitely after node is not kept anymore.
+    pub async fn changed(&mut self) -> Option<String> {
+        if self.path.changed().await.is_err() {
+            std::future::pending().await
+        }
+        self.path.borrow_and_update().clone()
+    }
+
+    /// Updates desired data of node, it is written to node in background.
+    pub fn set_data(&self, data: Vec<u8>) {
+        self.data.send_replace(data);
+    }
+
+    /// Stops keeping node and deletes it.
+    pub async fn close(self) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.closer.send(sender).ignore();
+        receiver.await.unwrap_or(Ok(()))
+    }
+}
+
+struct NodeKeeper {
+    client: Client,
+    state_watcher: StateWatcher,
+    prefix: String,
+    options: OwnedCreateOptions,
+    data: watch::Receiver<Vec<u8>>,
+    path: watch::Sender<Option<String>>,
+    current: Option<String>,
+    outdated: bool,
+}
+
+impl NodeKeeper {
+    fn node_path(&self, sequence: CreateSequence) -> String {
+        if self.options.mode.is_sequential() {
+            format!("{}{}", self.prefix, sequence)
+        } else {
+            self.prefix.clone()
+        }
+    }
+
+    fn is_owned(&self, stat: &Stat) -> bool {
+        !self.options.mode.is_ephemeral() || stat.ephemeral_owner == self.client.session_id().0
+    }
+
+    fn set_path(&mut self, path: Option<String>) {
+        if path != self.current {
+            self.current = path.clone();
+            self.path.send_replace(path);
+        }
+    }
+
+    async fn tick(interval: &mut Option<Interval>) {
+        match interval {
+            None => std::future::pending().await,
+            Some(interval) => interval.tick().await,
+        };
+    }
+
+    /// Finds ephemeral sequential node created by this instance in case of connection loss.
+    async fn find_created(&mut self) -> Result<Option<String>> {
+        if !self.options.mode.is_sequential() || !self.options.mode.is_ephemeral() {
+            return Ok(None);
+        }
+        let (parent, tree, name) = util::split_path(&self.prefix);
+        let children = loop {
+            match self.client.list_children(parent).await {
+                Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                result => break result?,
+            }
+        };
+        for child in children.iter().filter(|child| child.starts_with(name)) {
+            let path = format!("{}{}", tree, child);
+            let stat = loop {
+                match self.client.check_stat(&path).await {
+                    Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                    result => break result?,
+                }
+            };
+            match stat {
+                Some(stat) if stat.ephemeral_owner == self.client.session_id().0 => return Ok(Some(path)),
+                _ => continue,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds node which could be created by creation cancelled in closing.
+    async fn find_cancelled(&mut self) -> Result<Option<String>> {
+        if self.options.mode.is_sequential() {
+            return self.find_created().await;
+        }
+        loop {
+            match self.client.check_stat(&self.prefix).await {
+                Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+                Ok(Some(stat)) if self.is_owned(&stat) => return Ok(Some(self.prefix.clone())),
+                Ok(_) => return Ok(None),
+            }
+        }
+    }
+
+    /// Creates node with latest data and returns its path.
+    async fn create(&mut self) -> Result<String> {
+        loop {
+            let data = self.data.borrow_and_update().clone();
+            match self.client.create(&self.prefix, &data, &self.options.to_options()).await {
+                Ok((_stat, sequence)) => {
+                    self.outdated = false;
+                    return Ok(self.node_path(sequence));
+                },
+                Err(Error::ConnectionLoss) => {
+                    self.state_watcher.wait_connected().await?;
+                    if let Some(path) = self.find_created().await? {
+                        self.outdated = false;
+                        return Ok(path);
+                    }
+                },
+                Err(Error::NodeExists) => {
+                    let (stat, watcher) = match self.client.check_and_watch_stat(&self.prefix).await {
+                        Err(Error::ConnectionLoss) => {
+                            self.state_watcher.wait_connected().await?;
+                            continue;
+                        },
+                        Err(err) => return Err(err),
+                        Ok((None, _)) => continue,
+                        Ok((Some(stat), watcher)) => (stat, watcher),
+                    };
+                    if self.is_owned(&stat) {
+                        self.outdated = true;
+                        return Ok(self.prefix.clone());
+                    }
+                    // Ephemeral node of other session, it is probably an expired one.
+                    watcher.changed().await;
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Creates node if it is absent, otherwise watches it until it needs attention.
+    async fn maintain(&mut self) -> Result<()> {
+        let Some(path) = self.current.clone() else {
+            let path = self.create().await?;
+            self.set_path(Some(path));
+            return Ok(());
+        };
+        let (stat, watcher) = match self.client.check_and_watch_stat(&path).await {
+            Err(Error::ConnectionLoss) => return self.state_watcher.wait_connected().await,
+            Err(err) => return Err(err),
+            Ok((None, _)) => {
+                self.set_path(None);
+                return Ok(());
+            },
+            Ok((Some(stat), watcher)) => (stat, watcher),
+        };
+        if !self.is_owned(&stat) {
+            // Ephemeral node of expired session.
+            self.set_path(None);
+            return Ok(());
+        }
+        if self.outdated {
+            let data = self.data.borrow_and_update().clone();
+            match self.client.set_data(&path, &data, None).await {
+                // Watcher will tell deletion.
+                Ok(_) | Err(Error::NoNode) => self.outdated = false,
+                Err(Error::ConnectionLoss) => return self.state_watcher.wait_connected().await,
+                Err(err) => return Err(err),
+            }
+        }
+        let mut refresh = self.options.ttl.map(|ttl| time::interval_at(Instant::now() + ttl / 2, ttl / 2));
+        let changed = watcher.changed();
+        tokio::pin!(changed);
+        select! {
+            event = &mut changed => match event.event_type {
+                EventType::NodeDeleted => self.set_path(None),
+                EventType::Session => self.state_watcher.wait_connected().await?,
+                _ => {},
+            },
+            r = self.data.changed() => if r.is_ok() {
+                self.outdated = true;
+            },
+            _ = Self::tick(&mut refresh) => self.outdated = true,
+        }
+        Ok(())
+    }
+
+    async fn delete(&mut self, path: &str) -> Result<()> {
+        loop {
+            match self.client.delete(path, None).await {
+                Ok(_) | Err(Error::NoNode) => return Ok(()),
+                Err(Error::ConnectionLoss) => self.state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn keep(mut self, mut closing: oneshot::Receiver<oneshot::Sender<Result<()>>>) {
+        let responser = loop {
+            select! {
+                biased;
+                r = &mut closing => break r.ok(),
+                r = self.maintain() => if let Err(err) = r {
+                    log::warn!("ZooKeeper stops keeping node {} due to {}", self.prefix, err);
+                    self.set_path(None);
+                    return;
+                },
+            }
+        };
+        let path = match self.current.take() {
+            None => self.find_cancelled().await,
+            path => Ok(path),
+        };
+        let result = match path {
+            Ok(None) => Ok(()),
+            Ok(Some(path)) => self.delete(&path).await,
+            Err(err) => Err(err),
+        };
+        if let Some(responser) = responser {
+            responser.send(result).ignore();
+        }
+    }
+}
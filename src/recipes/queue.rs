@@ -0,0 +1,298 @@
+use super::{OwnedCreateOptions, OwnedRecipeOptions, RecipeOptions};
+use crate::acl::Acls;
+use crate::client::{Client, CreateMode, OneshotWatcher, StateWatcher};
+use crate::error::Error;
+use crate::proto::Stat;
+use crate::util;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const QUEUE_PREFIX: &str = "qn-";
+const PRIORITY_QUEUE_PREFIX: &str = "queue-";
+
+/// Items of queue are persistent sequential nodes, which are ordered by their names.
+#[derive(Clone, Debug)]
+struct QueueCore {
+    client: Client,
+    dir: String,
+    prefix: &'static str,
+    options: OwnedRecipeOptions,
+}
+
+impl QueueCore {
+    fn new(client: &Client, dir: &str, prefix: &'static str, options: RecipeOptions<'_>) -> Result<Self> {
+        util::validate_path(Default::default(), dir, false)?;
+        if options.acls.is_empty() {
+            return Err(Error::InvalidAcl);
+        }
+        Ok(Self { client: client.clone(), dir: dir.to_string(), prefix, options: OwnedRecipeOptions::new(&options) })
+    }
+
+    async fn offer(&self, name: &str, data: &[u8]) -> Result<String> {
+        let prefix = format!("{}/{}", self.dir, name);
+        let options = CreateMode::PersistentSequential.with_acls(Acls::new(&self.options.acls));
+        loop {
+            match self.client.create(&prefix, data, &options).await {
+                Ok((_stat, sequence)) => return Ok(format!("{}{}", prefix, sequence)),
+                Err(Error::NoNode) => {
+                    let Some(options) = self.options.parent.as_ref().map(OwnedCreateOptions::to_options) else {
+                        return Err(Error::NoNode);
+                    };
+                    self.client.create_ancestor_backword(&self.dir, &options).await?;
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Lists items in order, watches queue directory for changes if `watch` is true.
+    async fn list_items(&self, watch: bool) -> Result<(Vec<String>, Option<OneshotWatcher>)> {
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            let result = if watch {
+                self.client
+                    .list_and_watch_children(&self.dir)
+                    .await
+                    .map(|(children, watcher)| (children, Some(watcher)))
+            } else {
+                self.client.list_children(&self.dir).await.map(|children| (children, None))
+            };
+            let (mut children, watcher) = match result {
+                Err(Error::ConnectionLoss) => {
+                    state_watcher.wait_connected().await?;
+                    continue;
+                },
+                Err(Error::NoNode) if !watch => return Ok((Vec::new(), None)),
+                Err(Error::NoNode) => match self.client.check_and_watch_stat(&self.dir).await {
+                    Err(Error::ConnectionLoss) => {
+                        state_watcher.wait_connected().await?;
+                        continue;
+                    },
+                    Ok((Some(_), _)) => continue,
+                    Err(err) => return Err(err),
+                    Ok((None, watcher)) => return Ok((Vec::new(), Some(watcher))),
+                },
+                Err(err) => return Err(err),
+                Ok(result) => result,
+            };
+            children.retain(|child| child.starts_with(self.prefix));
+            children.sort_unstable();
+            return Ok((children, watcher));
+        }
+    }
+
+    async fn peek(&self) -> Result<Option<Vec<u8>>> {
+        let (children, _) = self.list_items(false).await?;
+        let mut state_watcher = self.client.state_watcher();
+        for child in children {
+            let path = format!("{}/{}", self.dir, child);
+            loop {
+                match self.client.get_data(&path).await {
+                    Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                    Err(Error::NoNode) => break,
+                    Err(err) => return Err(err),
+                    Ok((data, _stat)) => return Ok(Some(data)),
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Deletes item of given stat, returns false if it is claimed by others.
+    ///
+    /// [Error::ConnectionLoss] is returned if it is unknown whether item is deleted by us or others.
+    async fn remove(&self, path: &str, stat: &Stat, state_watcher: &mut StateWatcher) -> Result<bool> {
+        loop {
+            match self.client.delete(path, Some(stat.version)).await {
+                Ok(_) => return Ok(true),
+                // Claimed by others, or updated which is weird but fine to skip.
+                Err(Error::NoNode | Error::BadVersion) => return Ok(false),
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+            // Deletion could be committed before connection loss.
+            let current = loop {
+                match self.client.check_stat(path).await {
+                    Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                    result => break result?,
+                }
+            };
+            match current {
+                Some(current) if current.czxid == stat.czxid && current.version == stat.version => continue,
+                Some(_) => return Ok(false),
+                None => return Err(Error::ConnectionLoss),
+            }
+        }
+    }
+
+    /// Claims first available item in given items.
+    async fn claim(&self, children: Vec<String>) -> Result<Option<Vec<u8>>> {
+        let mut state_watcher = self.client.state_watcher();
+        for child in children {
+            let path = format!("{}/{}", self.dir, child);
+            let item = loop {
+                match self.client.get_data(&path).await {
+                    Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                    Err(Error::NoNode) => break None,
+                    result => break Some(result?),
+                }
+            };
+            let Some((data, stat)) = item else {
+                continue;
+            };
+            if self.remove(&path, &stat, &mut state_watcher).await? {
+                return Ok(Some(data));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn poll(&self) -> Result<Option<Vec<u8>>> {
+        let (children, _) = self.list_items(false).await?;
+        self.claim(children).await
+    }
+
+    async fn take(&self) -> Result<Vec<u8>> {
+        loop {
+            let (children, watcher) = self.list_items(true).await?;
+            if let Some(data) = self.claim(children).await? {
+                return Ok(data);
+            }
+            if let Some(watcher) = watcher {
+                watcher.changed().await;
+            }
+        }
+    }
+}
+
+/// FIFO queue compatible with `SimpleDistributedQueue` in Apache Curator.
+///
+/// Items are persistent sequential nodes with prefix `qn-` under queue directory.
+///
+/// # Cancellation safety
+/// Item could be lost if [DistributedQueue::take] or [DistributedQueue::poll] is cancelled after
+/// claiming the item.
+#[derive(Clone, Debug)]
+pub struct DistributedQueue {
+    core: QueueCore,
+}
+
+impl DistributedQueue {
+    /// Constructs queue on given directory.
+    ///
+    /// [RecipeOptions] specifies acls for item nodes and options to create queue directory and its
+    /// ancestors if they are absent.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `dir` is not a valid path.
+    /// * [Error::InvalidAcl] if acl is empty.
+    pub fn new<'a>(client: &Client, dir: &str, options: impl Into<RecipeOptions<'a>>) -> Result<Self> {
+        Ok(Self { core: QueueCore::new(client, dir, QUEUE_PREFIX, options.into())? })
+    }
+
+    /// Appends item to queue and returns its path.
+    ///
+    /// # Notable errors
+    /// * [Error::ConnectionLoss] if it is unknown whether item has been appended or not.
+    pub async fn offer(&self, data: &[u8]) -> Result<String> {
+        self.core.offer(QUEUE_PREFIX, data).await
+    }
+
+    /// Returns data of head item without removing it, `None` if queue is empty.
+    pub async fn peek(&self) -> Result<Option<Vec<u8>>> {
+        self.core.peek().await
+    }
+
+    /// Removes head item and returns its data, `None` if queue is empty.
+    ///
+    /// # Notable errors
+    /// * [Error::ConnectionLoss] if it is unknown whether head item has been removed by this call.
+    pub async fn poll(&self) -> Result<Option<Vec<u8>>> {
+        self.core.poll().await
+    }
+
+    /// Removes head item and returns its data, waits if queue is empty.
+    ///
+    /// # Notable errors
+    /// Same as [DistributedQueue::poll].
+    pub async fn take(&self) -> Result<Vec<u8>> {
+        self.core.take().await
+    }
+}
+
+/// Priority queue with layout of `DistributedPriorityQueue` in Apache Curator.
+///
+/// Items are persistent sequential nodes with prefix `queue-` and encoded priority under queue
+/// directory, so they are ordered by priority and then insertion. Lower value has higher priority.
+///
+/// Items are stored as is, so they are not compatible with serialization of Curator's queue.
+///
+/// # Cancellation safety
+/// Same as [DistributedQueue].
+#[derive(Clone, Debug)]
+pub struct DistributedPriorityQueue {
+    core: QueueCore,
+}
+
+impl DistributedPriorityQueue {
+    /// Constructs priority queue on given directory.
+    ///
+    /// [RecipeOptions] specifies acls for prioritized item nodes and options to create queue
+    /// directory and its ancestors if they are absent.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `dir` is not a valid path.
+    /// * [Error::InvalidAcl] if acl is empty.
+    pub fn new<'a>(client: &Client, dir: &str, options: impl Into<RecipeOptions<'a>>) -> Result<Self> {
+        Ok(Self { core: QueueCore::new(client, dir, PRIORITY_QUEUE_PREFIX, options.into())? })
+    }
+
+    /// Encodes priority so that lexicographical order is same as numerical order.
+    fn priority_name(priority: i32) -> String {
+        let sign = if priority >= 0 { 1 } else { 0 };
+        format!("{}{}{:08X}", PRIORITY_QUEUE_PREFIX, sign, priority as u32)
+    }
+
+    /// Appends item with given priority to queue and returns its path.
+    ///
+    /// # Notable errors
+    /// * [Error::ConnectionLoss] if it is unknown whether item has been appended or not.
+    pub async fn offer(&self, data: &[u8], priority: i32) -> Result<String> {
+        self.core.offer(&Self::priority_name(priority), data).await
+    }
+
+    /// Same as [DistributedQueue::peek].
+    pub async fn peek(&self) -> Result<Option<Vec<u8>>> {
+        self.core.peek().await
+    }
+
+    /// Same as [DistributedQueue::poll].
+    pub async fn poll(&self) -> Result<Option<Vec<u8>>> {
+        self.core.poll().await
+    }
+
+    /// Same as [DistributedQueue::take].
+    pub async fn take(&self) -> Result<Vec<u8>> {
+        self.core.take().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_priority_name() {
+        assert_eq!(DistributedPriorityQueue::priority_name(0), "queue-100000000");
+        assert_eq!(DistributedPriorityQueue::priority_name(10), "queue-10000000A");
+        assert_eq!(DistributedPriorityQueue::priority_name(-1), "queue-0FFFFFFFF");
+        let mut names: Vec<_> =
+            [5, i32::MIN, -10, i32::MAX, 0, -1].into_iter().map(DistributedPriorityQueue::priority_name).collect();
+        names.sort();
+        let expected: Vec<_> =
+            [i32::MIN, -10, -1, 0, 5, i32::MAX].into_iter().map(DistributedPriorityQueue::priority_name).collect();
+        assert_eq!(names, expected);
+    }
+}
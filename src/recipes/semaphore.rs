@@ -0,0 +1,182 @@
+use super::OwnedLockOptions;
+use crate::client::{Client, CreateMode, LockOptions, LockPrefix};
+use crate::error::Error;
+use crate::util;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Counting semaphore compatible with `InterProcessSemaphoreV2` in Apache Curator.
+///
+/// Leases are ephemeral sequential nodes under `{path}/leases` and contenders are serialized by
+/// lock under `{path}/locks`. All participants must agree on maximum number of leases, as it is
+/// not stored in ZooKeeper.
+#[derive(Clone, Debug)]
+pub struct InterProcessSemaphore {
+    client: Client,
+    locks: String,
+    leases: String,
+    max_leases: usize,
+    options: OwnedLockOptions,
+}
+
+/// Guard of lease acquired from [InterProcessSemaphore], its node is deleted in background when
+/// dropped.
+#[derive(Debug)]
+pub struct Lease {
+    client: Client,
+    path: String,
+}
+
+impl Lease {
+    /// Path of lease node.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns lease by deleting its node in foreground.
+    pub async fn release(mut self) -> Result<()> {
+        let path = std::mem::take(&mut self.path);
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.delete(&path, None).await {
+                Ok(_) | Err(Error::NoNode) => return Ok(()),
+                Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Deletes lease node in background.
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if self.path.is_empty() {
+            return;
+        }
+        let path = std::mem::take(&mut self.path);
+        self.client.clone().delete_background(path);
+    }
+}
+
+struct CreatingGuard<'a> {
+    client: &'a Client,
+    prefix: &'a str,
+}
+
+impl Drop for CreatingGuard<'_> {
+    fn drop(&mut self) {
+        self.client.clone().delete_ephemeral_background(self.prefix.to_string(), true);
+    }
+}
+
+impl InterProcessSemaphore {
+    /// Constructs semaphore on given path with given maximum number of leases.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `path` is invalid or `max_leases` is zero.
+    /// * [Error::InvalidAcl] if acl is empty.
+    pub fn new<'a>(
+        client: &Client,
+        path: &str,
+        max_leases: usize,
+        options: impl Into<LockOptions<'a>>,
+    ) -> Result<Self> {
+        let locks = format!("{}/locks", path);
+        LockPrefix::new_curator(&locks, "lock-")?;
+        if max_leases == 0 {
+            return Err(Error::BadArguments(&"semaphore must have at least one lease"));
+        }
+        let options = options.into();
+        if options.acls.is_empty() {
+            return Err(Error::InvalidAcl);
+        }
+        Ok(Self {
+            client: client.clone(),
+            leases: format!("{}/leases", path),
+            locks,
+            max_leases,
+            options: OwnedLockOptions::new(&options),
+        })
+    }
+
+    /// Maximum number of leases.
+    pub fn max_leases(&self) -> usize {
+        self.max_leases
+    }
+
+    /// Creates lease node and returns its path.
+    async fn create_lease(&self, data: &[u8]) -> Result<String> {
+        let prefix = format!("{}/_c_{}-lease-", self.leases, uuid::Uuid::new_v4());
+        let guard = CreatingGuard { client: &self.client, prefix: &prefix };
+        let options = CreateMode::EphemeralSequential.with_acls(self.options.to_options().acls);
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            match self.client.create(&prefix, data, &options).await {
+                Ok((_stat, sequence)) => {
+                    std::mem::forget(guard);
+                    return Ok(format!("{}{}", prefix, sequence));
+                },
+                Err(Error::ConnectionLoss) => {
+                    state_watcher.wait_connected().await?;
+                    let (_, tree, name) = util::split_path(&prefix);
+                    let children = loop {
+                        match self.client.list_children(&self.leases).await {
+                            Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                            result => break result?,
+                        }
+                    };
+                    if let Some(child) = children.iter().find(|child| child.starts_with(name)) {
+                        std::mem::forget(guard);
+                        return Ok(format!("{}{}", tree, child));
+                    }
+                },
+                Err(Error::NoNode) => {
+                    // Lock directory was created with ancestors before, so does the parent.
+                    let Some(parent) = &self.options.parent else {
+                        return Err(Error::NoNode);
+                    };
+                    match self.client.create(&self.leases, Default::default(), &parent.to_options()).await {
+                        Ok(_) | Err(Error::NodeExists) => continue,
+                        Err(Error::ConnectionLoss) => state_watcher.wait_connected().await?,
+                        Err(err) => return Err(err),
+                    }
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Acquires one lease with given data, waits if there are no available leases.
+    ///
+    /// # Notable errors
+    /// * [Error::RuntimeInconsistent] if lease node is deleted during acquisition.
+    /// * [Error::SessionExpired] if session expired before lease acquired.
+    /// * [Error::NoNode] if ancestor nodes do not exist and no options to create them.
+    ///
+    /// # Cancellation safety
+    /// This method is cancellation safe, nodes created in acquisition are deleted in background.
+    pub async fn acquire(&self, data: &[u8]) -> Result<Lease> {
+        let prefix = LockPrefix::new_curator(&self.locks, "lock-")?;
+        let lock = self.client.lock(prefix, Default::default(), self.options.to_options()).await?;
+        let lease = Lease { client: self.client.clone(), path: self.create_lease(data).await? };
+        let (_, _, name) = util::split_path(&lease.path);
+        let mut state_watcher = self.client.state_watcher();
+        loop {
+            let (children, watcher) = match self.client.list_and_watch_children(&self.leases).await {
+                Err(Error::ConnectionLoss) => {
+                    state_watcher.wait_connected().await?;
+                    continue;
+                },
+                result => result?,
+            };
+            if !children.iter().any(|child| child == name) {
+                return Err(Error::RuntimeInconsistent);
+            } else if children.len() <= self.max_leases {
+                break;
+            }
+            watcher.changed().await;
+        }
+        drop(lock);
+        Ok(lease)
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use super::driver::{Action, CacheDriver, CacheSource, CachedNodes, Change, FetchFuture, NodeMap};
+use super::BATCH_SIZE;
+use crate::client::{AddWatchMode, Client, MultiReadResult};
+use crate::error::Error;
+use crate::proto::Stat;
+use crate::session::{EventType, WatchedEvent};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Change of [TreeCache].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeCacheEvent {
+    /// Node is added to cache.
+    Added { path: String, data: Vec<u8>, stat: Stat },
+
+    /// Node in cache is updated to given data and stat.
+    Updated { path: String, data: Vec<u8>, stat: Stat },
+
+    /// Node is removed from cache, data and stat are the last cached ones.
+    Removed { path: String, data: Vec<u8>, stat: Stat },
+
+    /// Initial nodes have been added to cache.
+    Initialized,
+}
+
+/// Cache of a subtree, similar to `CuratorCache` in Apache Curator.
+///
+/// Cache bootstraps subtree with [crate::MultiReader] and applies events from persistent
+/// recursive watch, see [Client::watch]. Subtree is refetched after reconnection as persistent
+/// watch could lose events, see [ZOOKEEPER-4698][]. Cache is not updated anymore after session
+/// terminated, say, expired without recreation.
+///
+/// [ZOOKEEPER-4698]: https://issues.apache.org/jira/browse/ZOOKEEPER-4698
+#[derive(Debug)]
+pub struct TreeCache {
+    root: String,
+    cache: CachedNodes<(Vec<u8>, Stat), TreeCacheEvent>,
+}
+
+impl TreeCache {
+    /// Fetches subtree of given path, which could be absent, and keeps it current in background
+    /// until this instance dropped.
+    ///
+    /// # Notable errors
+    /// * [Error::BadArguments] if `root` is not a valid path.
+    /// * [Error::NoAuth] if subtree is not readable.
+    pub async fn new(client: &Client, root: &str) -> Result<Self> {
+        crate::util::validate_path(Default::default(), root, false)?;
+        let cache = CacheDriver::start(client, TreeSource { root: root.to_string() }).await?;
+        Ok(Self { root: root.to_string(), cache })
+    }
+
+    /// Root path of cached subtree.
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Data and stat of cached node.
+    pub fn get(&self, path: &str) -> Option<(Vec<u8>, Stat)> {
+        self.cache.nodes().get(path).cloned()
+    }
+
+    /// Paths of all cached nodes in lexicographical order.
+    pub fn paths(&self) -> Vec<String> {
+        self.cache.nodes().keys().cloned().collect()
+    }
+
+    /// Snapshot of all cached nodes.
+    pub fn snapshot(&self) -> BTreeMap<String, (Vec<u8>, Stat)> {
+        self.cache.nodes().clone()
+    }
+
+    /// Waits for next change of cache.
+    ///
+    /// Events are queued, so none of them are missed. This method will block indefinitely after
+    /// all events consumed and cache stopped updating.
+    pub async fn changed(&mut self) -> TreeCacheEvent {
+        self.cache.changed().await
+    }
+}
+
+struct TreeSource {
+    root: String,
+}
+
+impl TreeSource {
+    fn join_path(parent: &str, child: &str) -> String {
+        if parent == "/" {
+            format!("/{}", child)
+        } else {
+            format!("{}/{}", parent, child)
+        }
+    }
+
+    /// Fetches whole subtree level by level.
+    async fn fetch_tree(&self, client: &Client) -> Result<NodeMap<(Vec<u8>, Stat)>> {
+        let mut nodes = NodeMap::new();
+        let mut level = vec![self.root.clone()];
+        while !level.is_empty() {
+            let mut next_level = Vec::new();
+            for paths in level.chunks(BATCH_SIZE) {
+                let mut reader = client.new_multi_reader();
+                for path in paths {
+                    reader.add_get_data(path)?;
+                    reader.add_get_children(path)?;
+                }
+                let mut results = reader.commit().await?.into_iter();
+                for path in paths {
+                    match (results.next(), results.next()) {
+                        (Some(MultiReadResult::Data { data, stat }), Some(MultiReadResult::Children { children })) => {
+                            next_level.extend(children.iter().map(|child| Self::join_path(path, child)));
+                            nodes.insert(path.clone(), (data, stat));
+                        },
+                        (Some(MultiReadResult::Error { err }), _) | (_, Some(MultiReadResult::Error { err }))
+                            if err != Error::NoNode =>
+                        {
+                            return Err(err)
+                        },
+                        // Deleted in between.
+                        _ => continue,
+                    }
+                }
+            }
+            level = next_level;
+        }
+        Ok(nodes)
+    }
+}
+
+impl CacheSource for TreeSource {
+    type Event = TreeCacheEvent;
+    type Value = (Vec<u8>, Stat);
+
+    const INITIALIZED: TreeCacheEvent = TreeCacheEvent::Initialized;
+
+    fn path(&self) -> &str {
+        &self.root
+    }
+
+    fn mode(&self) -> AddWatchMode {
+        AddWatchMode::PersistentRecursive
+    }
+
+    fn fetch<'a>(&'a self, client: &'a Client) -> FetchFuture<'a, Self::Value> {
+        Box::pin(self.fetch_tree(client))
+    }
+
+    fn action(&self, event: &WatchedEvent) -> Action {
+        match event.event_type {
+            EventType::NodeCreated | EventType::NodeDataChanged => Action::Fetch,
+            EventType::NodeDeleted => Action::Remove,
+            _ => Action::Skip,
+        }
+    }
+
+    fn value(data: Vec<u8>, stat: Stat) -> Self::Value {
+        (data, stat)
+    }
+
+    fn stat((_, stat): &Self::Value) -> Option<&Stat> {
+        Some(stat)
+    }
+
+    fn event(change: Change, path: String, (data, stat): Self::Value) -> TreeCacheEvent {
+        match change {
+            Change::Added => TreeCacheEvent::Added { path, data, stat },
+            Change::Updated => TreeCacheEvent::Updated { path, data, stat },
+            Change::Removed => TreeCacheEvent::Removed { path, data, stat },
+        }
+    }
+}
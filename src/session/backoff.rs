@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+/// Exponential backoff with jitter between rounds of reconnection attempts.
+///
+/// Session reconnects immediately in first round after disconnected. Delay before following
+/// rounds starts from `base` and doubles up to `max`, and is bounded by session expiry deadline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReconnectBackoff {
+    base: Duration,
+    max: Duration,
+    jitter: f64,
+}
+
+impl ReconnectBackoff {
+    /// Constructs backoff with delay doubling from `base` up to `max` and jitter of `0.5`.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self { base, max, jitter: 0.5 }
+    }
+
+    /// Specifies ratio of delay to randomize, so delay is chosen uniformly from
+    /// `[(1 - jitter) * delay, delay]`. It is clamped to `[0, 1]`.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), &'static &'static str> {
+        if self.base.is_zero() {
+            return Err(&"reconnect backoff base delay must be positive");
+        } else if self.max < self.base {
+            return Err(&"reconnect backoff max delay must not be less than base delay");
+        }
+        Ok(())
+    }
+
+    /// Computes delay after given number of failed rounds.
+    pub(crate) fn delay(&self, failed_rounds: u32) -> Duration {
+        let exponent = failed_rounds.saturating_sub(1).min(31);
+        let delay = self.base.saturating_mul(1 << exponent).min(self.max);
+        delay.mul_f64(1.0 - self.jitter * rand::random::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let backoff = ReconnectBackoff::new(Duration::from_millis(100), Duration::from_secs(1)).with_jitter(0.0);
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(200));
+        assert_eq!(backoff.delay(4), Duration::from_millis(800));
+        assert_eq!(backoff.delay(5), Duration::from_secs(1));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_delay_jitter() {
+        let backoff = ReconnectBackoff::new(Duration::from_millis(100), Duration::from_secs(1));
+        for _ in 0..100 {
+            let delay = backoff.delay(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let backoff = ReconnectBackoff::new(Duration::ZERO, Duration::from_secs(1));
+        assert!(backoff.validate().is_err());
+        let backoff = ReconnectBackoff::new(Duration::from_secs(2), Duration::from_secs(1));
+        assert!(backoff.validate().is_err());
+        let backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(1));
+        assert!(backoff.validate().is_ok());
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::{self, IoSlice};
+#[cfg(feature = "tls")]
+use std::io::{Read, Write};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+#[cfg(feature = "tls")]
+use rustls::{ClientConfig, ClientConnection, ServerName};
+use tokio::net::TcpStream;
+
+use crate::host::ServerAddress;
+
+/// Connection to ZooKeeper server, either plain tcp or tls over tcp.
+///
+/// Tls is driven in place by non-blocking io on the underlying tcp stream, so both variants share
+/// readiness based interface of [TcpStream].
+pub enum Connection {
+    Raw(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(TcpStream, Box<ClientConnection>),
+}
+
+impl Connection {
+    fn stream(&self) -> &TcpStream {
+        match self {
+            Self::Raw(stream) => stream,
+            #[cfg(feature = "tls")]
+            Self::Tls(stream, _) => stream,
+        }
+    }
+
+    pub async fn readable(&self) -> io::Result<()> {
+        self.stream().readable().await
+    }
+
+    pub async fn writable(&self) -> io::Result<()> {
+        self.stream().writable().await
+    }
+
+    /// Checks whether there are buffered data pending to write to underlying stream.
+    pub fn wants_write(&self) -> bool {
+        match self {
+            Self::Raw(_) => false,
+            #[cfg(feature = "tls")]
+            Self::Tls(_, tls) => tls.wants_write(),
+        }
+    }
+
+    pub fn try_read_buf(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        match self {
+            Self::Raw(stream) => stream.try_read_buf(buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream, tls) => Self::try_read_tls(stream, tls, buf),
+        }
+    }
+
+    /// Writes given buffers, accepted bytes could be buffered until [Connection::wants_write]
+    /// turns false.
+    pub fn try_write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        match self {
+            Self::Raw(stream) => stream.try_write_vectored(bufs),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream, tls) => {
+                Self::flush_tls(stream, tls)?;
+                let n = tls.writer().write_vectored(bufs)?;
+                match Self::flush_tls(stream, tls) {
+                    Err(err) if err.kind() != io::ErrorKind::WouldBlock => Err(err),
+                    _ => Ok(n),
+                }
+            },
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    fn try_read_tls(stream: &TcpStream, tls: &mut ClientConnection, buf: &mut Vec<u8>) -> io::Result<usize> {
+        match tls.read_tls(&mut SyncStream(stream)) {
+            Err(err) if err.kind() != io::ErrorKind::WouldBlock => return Err(err),
+            _ => {},
+        }
+        tls.process_new_packets().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let start = buf.len();
+        loop {
+            let len = buf.len();
+            buf.resize(len + 4096, 0);
+            match tls.reader().read(&mut buf[len..]) {
+                Ok(0) => {
+                    buf.truncate(len);
+                    return Ok(len - start);
+                },
+                Ok(n) => buf.truncate(len + n),
+                Err(err) => {
+                    buf.truncate(len);
+                    return match len - start {
+                        0 => Err(err),
+                        n => Ok(n),
+                    };
+                },
+            }
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    fn flush_tls(stream: &TcpStream, tls: &mut ClientConnection) -> io::Result<()> {
+        while tls.wants_write() {
+            tls.write_tls(&mut SyncStream(stream))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "tls")]
+    async fn handshake(stream: &TcpStream, tls: &mut ClientConnection) -> io::Result<()> {
+        while tls.is_handshaking() || tls.wants_write() {
+            if tls.wants_write() {
+                stream.writable().await?;
+                match Self::flush_tls(stream, tls) {
+                    Err(err) if err.kind() != io::ErrorKind::WouldBlock => return Err(err),
+                    _ => continue,
+                }
+            }
+            stream.readable().await?;
+            match tls.read_tls(&mut SyncStream(stream)) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                Ok(_) => {
+                    tls.process_new_packets().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                },
+                Err(err) if err.kind() != io::ErrorKind::WouldBlock => return Err(err),
+                Err(_) => {},
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Blocking io adapter for non-blocking [TcpStream].
+#[cfg(feature = "tls")]
+struct SyncStream<'a>(&'a TcpStream);
+
+#[cfg(feature = "tls")]
+impl Read for SyncStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.try_read(buf)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Write for SyncStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.try_write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.0.try_write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Establishes [Connection]s to ZooKeeper servers.
+#[derive(Clone, Default)]
+pub struct Connector {
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<ClientConfig>>,
+}
+
+impl Connector {
+    #[cfg(feature = "tls")]
+    pub fn with_tls(config: ClientConfig) -> Self {
+        Self { tls: Some(Arc::new(config)) }
+    }
+
+    /// Probes whether given server serves read-write requests using four letter word `isro`.
+    ///
+    /// `isro` is whitelisted if server is started with read-only mode enabled.
+    pub async fn probe_rw(&self, server: &ServerAddress) -> io::Result<bool> {
+        let mut sock = self.connect(server).await?;
+        let request = b"isro";
+        let mut written = 0;
+        while written < request.len() || sock.wants_write() {
+            sock.writable().await?;
+            match sock.try_write_vectored(&[IoSlice::new(&request[written..])]) {
+                Ok(n) => written += n,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {},
+                Err(err) => return Err(err),
+            }
+        }
+        let mut buf = Vec::with_capacity(2);
+        while buf.len() < 2 {
+            sock.readable().await?;
+            match sock.try_read_buf(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => {},
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {},
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(buf.starts_with(b"rw"))
+    }
+
+    pub async fn connect(&self, server: &ServerAddress) -> io::Result<Connection> {
+        let stream = TcpStream::connect(server.addr).await?;
+        #[cfg(feature = "tls")]
+        if let Some(config) = self.tls.as_ref() {
+            let server_name = ServerName::try_from(server.host.as_str())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            let mut tls = ClientConnection::new(config.clone(), server_name)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            Connection::handshake(&stream, &mut tls).await?;
+            return Ok(Connection::Tls(stream, Box::new(tls)));
+        }
+        Ok(Connection::Raw(stream))
+    }
+}
@@ -3,8 +3,8 @@ use std::io::{self, IoSlice};
 
 use hashbrown::HashMap;
 use strum::IntoEnumIterator;
-use tokio::net::TcpStream;
 
+use super::connection::Connection;
 use super::request::{MarshalledRequest, Operation, SessionOperation, StateResponser};
 use super::types::WatchMode;
 use super::xid::Xid;
@@ -182,7 +182,8 @@ impl Depot {
 
     pub fn push_remove_watch(&mut self, path: &str, mode: WatchMode, responser: StateResponser) {
         let record = RemoveWatchesRequest { path, mode: mode.into() };
-        let operation = SessionOperation { request: MarshalledRequest::new(OpCode::RemoveWatches, &record), responser };
+        let request = MarshalledRequest::new(OpCode::RemoveWatches, &record);
+        let operation = SessionOperation { request, responser, deadline: None };
         self.push_session(operation);
     }
 
@@ -192,7 +193,7 @@ impl Depot {
             .any(|mode| self.watching_paths.contains_key(&(path, mode)))
     }
 
-    pub fn write_operations(&mut self, sock: &TcpStream, session_id: SessionId) -> Result<(), Error> {
+    pub fn write_operations(&mut self, sock: &mut Connection, session_id: SessionId) -> Result<(), Error> {
         let result = sock.try_write_vectored(self.writing_slices.as_slice());
         let mut written_bytes = match result {
             Err(err) => {
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::Error;
+
+/// Limits outstanding requests, that is, requests sent by client but not yet responded.
+#[derive(Debug, Default)]
+pub struct RequestLimiter {
+    max_bytes: usize,
+    requests: Option<Arc<Semaphore>>,
+    bytes: Option<Arc<Semaphore>>,
+    outstanding: Arc<AtomicUsize>,
+}
+
+/// Permit to send one request, it is released after request responded or failed.
+#[derive(Debug)]
+pub struct RequestPermit {
+    _requests: Option<OwnedSemaphorePermit>,
+    _bytes: Option<OwnedSemaphorePermit>,
+    outstanding: Arc<AtomicUsize>,
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        self.outstanding.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RequestLimiter {
+    /// Constructs limiter with given maximum number and bytes of outstanding requests, zero
+    /// means no limit.
+    pub fn new(max_requests: usize, max_bytes: usize) -> Self {
+        let semaphore = |permits: usize| {
+            if permits == 0 {
+                None
+            } else {
+                Some(Arc::new(Semaphore::new(permits.min(Semaphore::MAX_PERMITS))))
+            }
+        };
+        let max_bytes = max_bytes.min(u32::MAX as usize);
+        Self {
+            max_bytes,
+            requests: semaphore(max_requests),
+            bytes: semaphore(max_bytes),
+            outstanding: Default::default(),
+        }
+    }
+
+    /// Number of outstanding requests.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.load(Ordering::Relaxed)
+    }
+
+    /// Bytes to acquire for request of given length, request exceeding limit is allowed to go
+    /// alone.
+    fn bytes_permits(&self, len: usize) -> u32 {
+        len.min(self.max_bytes) as u32
+    }
+
+    fn new_permit(&self, requests: Option<OwnedSemaphorePermit>, bytes: Option<OwnedSemaphorePermit>) -> RequestPermit {
+        self.outstanding.fetch_add(1, Ordering::Relaxed);
+        RequestPermit { _requests: requests, _bytes: bytes, outstanding: self.outstanding.clone() }
+    }
+
+    /// Acquires permit for request of given length without waiting.
+    pub fn try_acquire(&self, len: usize) -> Result<RequestPermit, Error> {
+        let requests = match &self.requests {
+            None => None,
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().map_err(|_| Error::TooManyRequests)?),
+        };
+        let bytes = match &self.bytes {
+            None => None,
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .try_acquire_many_owned(self.bytes_permits(len))
+                    .map_err(|_| Error::TooManyRequests)?,
+            ),
+        };
+        Ok(self.new_permit(requests, bytes))
+    }
+
+    /// Acquires permit for request of given length, waits if limits reached.
+    pub async fn acquire(self: Arc<Self>, len: usize) -> RequestPermit {
+        // Semaphores are never closed.
+        let requests = match &self.requests {
+            None => None,
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+        };
+        let bytes = match &self.bytes {
+            None => None,
+            Some(semaphore) => semaphore.clone().acquire_many_owned(self.bytes_permits(len)).await.ok(),
+        };
+        self.new_permit(requests, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_try_acquire() {
+        let limiter = RequestLimiter::new(2, 100);
+        let permit1 = limiter.try_acquire(10).unwrap();
+        let permit2 = limiter.try_acquire(10).unwrap();
+        assert_eq!(limiter.outstanding(), 2);
+        assert_eq!(limiter.try_acquire(10).unwrap_err(), Error::TooManyRequests);
+        drop(permit1);
+        assert_eq!(limiter.outstanding(), 1);
+
+        // Bytes exceed.
+        assert_eq!(limiter.try_acquire(91).unwrap_err(), Error::TooManyRequests);
+        let permit3 = limiter.try_acquire(90).unwrap();
+        drop(permit2);
+        drop(permit3);
+        assert_eq!(limiter.outstanding(), 0);
+
+        // Large request goes alone.
+        let permit = limiter.try_acquire(1000).unwrap();
+        assert_eq!(limiter.try_acquire(1).unwrap_err(), Error::TooManyRequests);
+        drop(permit);
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let limiter = RequestLimiter::default();
+        let permits: Vec<_> = (0..1000).map(|_| limiter.try_acquire(usize::MAX).unwrap()).collect();
+        assert_eq!(limiter.outstanding(), 1000);
+        drop(permits);
+        assert_eq!(limiter.outstanding(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_acquire() {
+        let limiter = Arc::new(RequestLimiter::new(1, 0));
+        let permit = limiter.clone().acquire(10).await;
+        let mut acquiring = tokio::spawn(limiter.clone().acquire(10));
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(10), &mut acquiring).await.is_err());
+        drop(permit);
+        let _permit = acquiring.await.unwrap();
+        assert_eq!(limiter.outstanding(), 1);
+    }
+}
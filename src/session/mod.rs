@@ -1,21 +1,31 @@
+mod backoff;
+mod connection;
 mod depot;
 mod event;
+mod limiter;
 mod request;
+mod sasl;
 mod types;
 mod watch;
 mod xid;
 
+use std::collections::VecDeque;
+use std::future::Future;
 use std::io;
+use std::pin::Pin;
 use std::time::Duration;
 
 use ignore_result::Ignore;
-use tokio::net::TcpStream;
 use tokio::select;
 use tokio::sync::mpsc;
 use tokio::time::{self, Instant, Sleep};
 
+pub use self::backoff::ReconnectBackoff;
+use self::connection::Connection;
+pub use self::connection::Connector;
 pub use self::depot::Depot;
 use self::event::WatcherEvent;
+pub use self::limiter::RequestLimiter;
 pub use self::request::{
     ConnectOperation,
     MarshalledRequest,
@@ -24,16 +34,34 @@ pub use self::request::{
     StateReceiver,
     StateResponser,
 };
-pub use self::types::{EventType, SessionId, SessionState, WatchedEvent};
+use self::sasl::SaslClient;
+pub use self::sasl::SaslOptions;
+pub use self::types::{EventType, SessionId, SessionInfo, SessionState, WatchedEvent};
 pub use self::watch::{OneshotReceiver, PersistentReceiver, WatchReceiver};
 use self::watch::{WatchManager, WatcherId};
 use crate::error::Error;
-use crate::proto::{AuthPacket, ConnectRequest, ConnectResponse, ErrorCode, OpCode, PredefinedXid, ReplyHeader};
+use crate::host::{HostProvider, ServerAddress};
+use crate::proto::{
+    AuthPacket,
+    ConnectRequest,
+    ConnectResponse,
+    ErrorCode,
+    OpCode,
+    PredefinedXid,
+    ReplyHeader,
+    SaslRequest,
+};
 use crate::record;
 
 pub const PASSWORD_LEN: usize = 16;
 pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(6);
 
+const MIN_RW_PROBE_DELAY: Duration = Duration::from_millis(100);
+const MAX_RW_PROBE_DELAY: Duration = Duration::from_secs(60);
+
+/// Probe to read-write server, resolves to the server if it is read-write.
+type RwProbe = Pin<Box<dyn Future<Output = Option<ServerAddress>> + Send>>;
+
 trait RequestOperation {
     fn into_responser(self) -> StateResponser;
 }
@@ -51,8 +79,18 @@ impl RequestOperation for (WatcherId, StateResponser) {
 }
 
 pub struct Session {
+    connector: Connector,
+    host_provider: Box<dyn HostProvider>,
+    servers: VecDeque<ServerAddress>,
+    server: Option<ServerAddress>,
+    reconnect_backoff: Option<ReconnectBackoff>,
+    failed_rounds: u32,
+    rw_probe_delay: Duration,
     readonly: bool,
     detached: bool,
+    recreate: bool,
+    recreating: bool,
+    reestablishments: u32,
 
     configured_connection_timeout: Duration,
 
@@ -72,29 +110,56 @@ pub struct Session {
     session_readonly: bool,
 
     pub authes: Vec<MarshalledRequest>,
-    state_sender: tokio::sync::watch::Sender<SessionState>,
+    sasl: Option<SaslOptions>,
+    sasl_client: Option<SaslClient>,
+    state_sender: tokio::sync::watch::Sender<(SessionState, u32)>,
+    info_sender: tokio::sync::watch::Sender<SessionInfo>,
 
     watch_manager: WatchManager,
     unwatch_receiver: Option<mpsc::UnboundedReceiver<(WatcherId, StateResponser)>>,
+
+    background_receiver: Option<mpsc::UnboundedReceiver<SessionOperation>>,
+    hosts_receiver: Option<mpsc::UnboundedReceiver<Vec<(String, u16)>>>,
 }
 
 impl Session {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        connector: Connector,
+        host_provider: Box<dyn HostProvider>,
         session: Option<(SessionId, Vec<u8>)>,
         authes: &[AuthPacket],
+        sasl: Option<SaslOptions>,
+        reconnect_backoff: Option<ReconnectBackoff>,
         readonly: bool,
         detached: bool,
+        recreate: bool,
         session_timeout: Duration,
         connection_timeout: Duration,
-    ) -> (Session, tokio::sync::watch::Receiver<SessionState>) {
+    ) -> (Session, tokio::sync::watch::Receiver<(SessionState, u32)>) {
         let (session_id, session_password) =
             session.unwrap_or_else(|| (SessionId(0), Vec::with_capacity(PASSWORD_LEN)));
-        let (state_sender, state_receiver) = tokio::sync::watch::channel(SessionState::Disconnected);
+        let (state_sender, state_receiver) = tokio::sync::watch::channel((SessionState::Disconnected, 0));
+        let (info_sender, _) = tokio::sync::watch::channel(SessionInfo {
+            id: session_id,
+            password: session_password.clone(),
+            timeout: session_timeout,
+        });
         let now = Instant::now();
         let (watch_manager, unwatch_receiver) = WatchManager::new();
         let mut session = Session {
+            connector,
+            host_provider,
+            servers: VecDeque::new(),
+            server: None,
+            reconnect_backoff,
+            failed_rounds: 0,
+            rw_probe_delay: MIN_RW_PROBE_DELAY,
             readonly,
             detached,
+            recreate,
+            recreating: false,
+            reestablishments: 0,
 
             configured_connection_timeout: connection_timeout,
 
@@ -114,15 +179,48 @@ impl Session {
             session_readonly: false,
 
             authes: authes.iter().map(|auth| MarshalledRequest::new(OpCode::Auth, auth)).collect(),
+            sasl,
+            sasl_client: None,
             state_sender,
+            info_sender,
             watch_manager,
             unwatch_receiver: Some(unwatch_receiver),
+
+            background_receiver: None,
+            hosts_receiver: None,
         };
         let timeout = if session_timeout.is_zero() { DEFAULT_SESSION_TIMEOUT } else { session_timeout };
         session.reset_timeout(timeout);
         (session, state_receiver)
     }
 
+    /// Subscribes to info of established session, it changes after session recreated.
+    pub fn subscribe_info(&self) -> tokio::sync::watch::Receiver<SessionInfo> {
+        self.info_sender.subscribe()
+    }
+
+    /// Creates requester for background tasks, e.g. server tracking and session hooks. Session
+    /// will not be kept alive by requests from it.
+    pub fn background_requester(&mut self) -> mpsc::UnboundedSender<SessionOperation> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.background_receiver = Some(receiver);
+        sender
+    }
+
+    /// Tracks servers for host provider, returns sender for hosts to update.
+    pub fn track_servers(&mut self) -> mpsc::UnboundedSender<Vec<(String, u16)>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.hosts_receiver = Some(receiver);
+        sender
+    }
+
+    async fn recv_optional<T>(receiver: &mut Option<mpsc::UnboundedReceiver<T>>) -> Option<T> {
+        match receiver {
+            None => std::future::pending().await,
+            Some(receiver) => receiver.recv().await,
+        }
+    }
+
     async fn close_requester<T: RequestOperation>(mut requester: mpsc::UnboundedReceiver<T>, err: &Error) {
         requester.close();
         while let Some(operation) = requester.recv().await {
@@ -133,8 +231,7 @@ impl Session {
 
     pub async fn serve(
         &mut self,
-        servers: Vec<(String, u16)>,
-        sock: TcpStream,
+        sock: Connection,
         mut buf: Vec<u8>,
         mut connecting_trans: Depot,
         mut requester: mpsc::UnboundedReceiver<SessionOperation>,
@@ -143,12 +240,11 @@ impl Session {
         let mut unwatch_requester = self.unwatch_receiver.take().unwrap();
         self.serve_once(sock, &mut buf, &mut depot, &mut requester, &mut unwatch_requester).await;
         while !self.session_state.is_terminated() {
-            let mut hosts = servers.iter().cycle().map(|(host, port)| (host.as_str(), *port));
-            let sock = match self.start(&mut hosts, &mut buf, &mut connecting_trans).await {
+            let sock = match self.start(usize::MAX, &mut buf, &mut connecting_trans).await {
                 Err(err) => {
-                    log::warn!("fail to connect to cluster {:?} due to {}", servers, err);
-                    self.resolve_start_error(&err);
-                    break;
+                    log::warn!("ZooKeeper session {} fails to connect to cluster due to {}", self.session_id, err);
+                    self.resolve_start_error(&err, &mut requester, &mut depot);
+                    continue;
                 },
                 Ok(sock) => sock,
             };
@@ -157,9 +253,37 @@ impl Session {
         let err = self.state_error();
         Self::close_requester(requester, &err).await;
         Self::close_requester(unwatch_requester, &err).await;
+        if let Some(background_requester) = self.background_receiver.take() {
+            Self::close_requester(background_requester, &err).await;
+        }
         depot.terminate(err);
     }
 
+    /// Checks whether there are still clients, received request is queued for next connection.
+    fn has_clients(requester: &mut mpsc::UnboundedReceiver<SessionOperation>, depot: &mut Depot) -> bool {
+        match requester.try_recv() {
+            Ok(operation) => {
+                Self::queue_request(operation, depot, Instant::now());
+                true
+            },
+            Err(mpsc::error::TryRecvError::Empty) => true,
+            Err(mpsc::error::TryRecvError::Disconnected) => false,
+        }
+    }
+
+    /// Drops expired session so a new one will be created in next connection.
+    fn recreate_session(&mut self) {
+        if !self.recreating {
+            log::info!("ZooKeeper session {} expired, will create new session", self.session_id);
+        }
+        self.watch_manager.dispatch_session_state(SessionState::Expired);
+        self.session_id = SessionId(0);
+        self.session_password.clear();
+        self.last_recv = Instant::now();
+        self.recreating = true;
+        self.change_state(SessionState::Disconnected);
+    }
+
     fn state_error(&self) -> Error {
         self.session_state.to_error()
     }
@@ -170,11 +294,21 @@ impl Session {
         }
         self.session_state = state;
         self.watch_manager.dispatch_session_state(state);
-        self.state_sender.send(state).ignore();
+        self.state_sender.send((state, self.reestablishments)).ignore();
     }
 
-    fn resolve_start_error(&mut self, err: &Error) {
+    fn resolve_start_error(
+        &mut self,
+        err: &Error,
+        requester: &mut mpsc::UnboundedReceiver<SessionOperation>,
+        depot: &mut Depot,
+    ) {
         let state = match err {
+            Error::SessionExpired | Error::SessionMoved | Error::Timeout
+                if self.recreate && Self::has_clients(requester, depot) =>
+            {
+                return self.recreate_session()
+            },
             Error::SessionExpired | Error::SessionMoved | Error::Timeout => SessionState::Expired,
             Error::AuthFailed => SessionState::AuthFailed,
             _ => SessionState::Closed,
@@ -184,6 +318,7 @@ impl Session {
 
     fn resolve_serve_error(&mut self, err: &Error) {
         let state = match err {
+            Error::SessionExpired | Error::SessionMoved if self.recreate => return self.recreate_session(),
             Error::SessionExpired | Error::SessionMoved => SessionState::Expired,
             Error::AuthFailed => SessionState::AuthFailed,
             Error::ClientClosed => SessionState::Closed,
@@ -194,13 +329,13 @@ impl Session {
 
     async fn serve_once(
         &mut self,
-        sock: TcpStream,
+        mut sock: Connection,
         buf: &mut Vec<u8>,
         depot: &mut Depot,
         requester: &mut mpsc::UnboundedReceiver<SessionOperation>,
         unwatch_requester: &mut mpsc::UnboundedReceiver<(WatcherId, StateResponser)>,
     ) {
-        if let Err(err) = self.serve_session(&sock, buf, depot, requester, unwatch_requester).await {
+        if let Err(err) = self.serve_session(&mut sock, buf, depot, requester, unwatch_requester).await {
             self.resolve_serve_error(&err);
             log::debug!("ZooKeeper session {} state {} error {}", self.session_id, self.session_state, err);
             depot.error(&err);
@@ -275,15 +410,15 @@ impl Session {
     }
 
     fn handle_reply(&mut self, header: ReplyHeader, body: &[u8], depot: &mut Depot) -> Result<(), Error> {
-        if header.err == ErrorCode::SessionExpired.into() {
+        if header.err == i32::from(ErrorCode::SessionExpired) {
             return Err(Error::SessionExpired);
-        } else if header.err == ErrorCode::AuthFailed.into() {
+        } else if header.err == i32::from(ErrorCode::AuthFailed) {
             return Err(Error::AuthFailed);
         }
-        if header.xid == PredefinedXid::Notification.into() {
+        if header.xid == i32::from(PredefinedXid::Notification) {
             self.handle_notification(body, depot)?;
             return Ok(());
-        } else if header.xid == PredefinedXid::Ping.into() {
+        } else if header.xid == i32::from(PredefinedXid::Ping) {
             depot.pop_ping()?;
             if let Some(last_ping) = self.last_ping.take() {
                 let elapsed = Instant::now() - last_ping;
@@ -292,10 +427,40 @@ impl Session {
             return Ok(());
         }
         let operation = depot.pop_request(header.xid)?;
+        if operation.request.get_code() == OpCode::Sasl {
+            return self.handle_sasl_reply(header.err, body, depot);
+        }
         self.handle_session_reply(operation, header.err, body, depot);
         Ok(())
     }
 
+    fn handle_sasl_reply(&mut self, rc: i32, mut body: &[u8], depot: &mut Depot) -> Result<(), Error> {
+        let client = match self.sasl_client.as_mut() {
+            None => return Err(Error::UnexpectedError("got sasl response while not authenticating".to_string())),
+            Some(client) => client,
+        };
+        if rc != i32::from(ErrorCode::Ok) {
+            log::warn!(
+                "ZooKeeper session {} fails {} authentication with error {}",
+                self.session_id,
+                client.mechanism(),
+                rc
+            );
+            return Err(Error::AuthFailed);
+        }
+        let challenge = record::unmarshal_entity::<&[u8]>(&"sasl challenge", &mut body)?;
+        match client.evaluate(challenge)? {
+            Some(token) => Self::send_sasl(&token, depot),
+            None => {
+                log::debug!("ZooKeeper session {} completes {} authentication", self.session_id, client.mechanism());
+                self.sasl_client = None;
+                self.send_authes(depot);
+                self.watch_manager.resend_watches(self.last_zxid, depot);
+            },
+        }
+        Ok(())
+    }
+
     fn calc_tick_timeout(&self, session_timeout: Duration) -> Duration {
         let connection_timeout = self.configured_connection_timeout;
         let tick_timeout = if connection_timeout.is_zero() || connection_timeout > session_timeout * 3 / 5 {
@@ -335,10 +500,25 @@ impl Session {
     }
 
     fn complete_connect(&mut self) {
+        if self.recreating {
+            self.recreating = false;
+            // Reestablishment is a single event, state watchers detect it by counting.
+            self.reestablishments += 1;
+            self.watch_manager.dispatch_session_state(SessionState::Reestablished);
+        }
         let state = if self.session_readonly { SessionState::ConnectedReadOnly } else { SessionState::SyncConnected };
         self.change_state(state);
     }
 
+    fn publish_info(&self) {
+        if self.info_sender.borrow().id == self.session_id {
+            return;
+        }
+        let info =
+            SessionInfo { id: self.session_id, password: self.session_password.clone(), timeout: self.session_timeout };
+        self.info_sender.send_replace(info);
+    }
+
     fn handle_connect_response(&mut self, mut body: &[u8]) -> Result<(), Error> {
         let response = record::unmarshal::<ConnectResponse>(&mut body)?;
         if response.session_id == 0 {
@@ -351,11 +531,12 @@ impl Session {
         self.session_password.clear();
         self.session_password.extend_from_slice(response.password);
         self.session_readonly = response.readonly;
+        self.publish_info();
         self.complete_connect();
         Ok(())
     }
 
-    fn read_socket(&mut self, sock: &TcpStream, buf: &mut Vec<u8>) -> Result<(), Error> {
+    fn read_socket(&mut self, sock: &mut Connection, buf: &mut Vec<u8>) -> Result<(), Error> {
         match sock.try_read_buf(buf) {
             Ok(0) => {
                 log::debug!("ZooKeeper session {} encounters server closed", self.session_id);
@@ -392,7 +573,12 @@ impl Session {
         Ok(())
     }
 
-    async fn serve_connecting(&mut self, sock: &TcpStream, buf: &mut Vec<u8>, depot: &mut Depot) -> Result<(), Error> {
+    async fn serve_connecting(
+        &mut self,
+        sock: &mut Connection,
+        buf: &mut Vec<u8>,
+        depot: &mut Depot,
+    ) -> Result<(), Error> {
         let mut tick = time::interval(self.tick_timeout);
         tick.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
         while !(self.session_state.is_connected() && depot.is_empty()) {
@@ -401,7 +587,7 @@ impl Session {
                     self.read_socket(sock, buf)?;
                     self.handle_recv_buf(buf, depot)?;
                 },
-                _ = sock.writable(), if depot.has_pending_writes() => {
+                _ = sock.writable(), if depot.has_pending_writes() || sock.wants_write() => {
                     depot.write_operations(sock, self.session_id)?;
                     self.last_send = Instant::now();
                 },
@@ -415,9 +601,35 @@ impl Session {
         Ok(())
     }
 
+    /// Pushes and writes request from client unless it has timed out.
+    fn push_request(
+        &mut self,
+        operation: SessionOperation,
+        sock: &mut Connection,
+        depot: &mut Depot,
+    ) -> Result<(), Error> {
+        let now = Instant::now();
+        if !Self::queue_request(operation, depot, now) {
+            return Ok(());
+        }
+        depot.write_operations(sock, self.session_id)?;
+        self.last_send = now;
+        Ok(())
+    }
+
+    /// Queues request unless it has passed its deadline, returns whether it is queued.
+    fn queue_request(operation: SessionOperation, depot: &mut Depot, now: Instant) -> bool {
+        if operation.is_expired(now) {
+            operation.responser.send(Err(Error::Timeout));
+            return false;
+        }
+        depot.push_session(operation);
+        true
+    }
+
     async fn serve_session(
         &mut self,
-        sock: &TcpStream,
+        sock: &mut Connection,
         buf: &mut Vec<u8>,
         depot: &mut Depot,
         requester: &mut mpsc::UnboundedReceiver<SessionOperation>,
@@ -426,18 +638,34 @@ impl Session {
         let mut tick = time::interval(self.tick_timeout);
         tick.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
         let mut channel_closed = false;
+        let mut rw_probe = if self.session_readonly { Some(self.probe_rw_server().await) } else { None };
+        // Read-write server to migrate to after draining of in-flight requests.
+        let mut rw_server: Option<ServerAddress> = None;
         depot.start();
         while !(channel_closed && depot.is_empty()) {
+            if rw_server.is_some() && depot.is_empty() {
+                let server = rw_server.take().unwrap();
+                log::info!("ZooKeeper session {} migrates to read-write server {}", self.session_id, server.addr);
+                self.servers.push_front(server);
+                return Err(Error::ConnectionLoss);
+            }
             select! {
+                r = async { rw_probe.as_mut().unwrap().await }, if rw_probe.is_some() => match r {
+                    None => rw_probe = Some(self.probe_rw_server().await),
+                    Some(server) => {
+                        rw_probe = None;
+                        rw_server = Some(server);
+                    },
+                },
                 _ = sock.readable() => {
                     self.read_socket(sock, buf)?;
                     self.handle_recv_buf(buf, depot)?;
                 },
-                _ = sock.writable(), if depot.has_pending_writes() => {
+                _ = sock.writable(), if depot.has_pending_writes() || sock.wants_write() => {
                     depot.write_operations(sock, self.session_id)?;
                     self.last_send = Instant::now();
                 },
-                r = requester.recv(), if !channel_closed => {
+                r = requester.recv(), if !channel_closed && rw_server.is_none() => {
                     let operation = if let Some(operation) = r {
                         operation
                     } else {
@@ -447,13 +675,22 @@ impl Session {
                         channel_closed = true;
                         continue;
                     };
-                    depot.push_session(operation);
-                    depot.write_operations(sock, self.session_id)?;
-                    self.last_send = Instant::now();
+                    self.push_request(operation, sock, depot)?;
                 },
                 r = unwatch_requester.recv() => if let Some((watcher_id, responser)) = r {
                     self.watch_manager.remove_watcher(watcher_id, responser, depot);
                 },
+                r = Self::recv_optional(&mut self.background_receiver), if !channel_closed && rw_server.is_none() => match r {
+                    None => self.background_receiver = None,
+                    Some(operation) => self.push_request(operation, sock, depot)?,
+                },
+                r = Self::recv_optional(&mut self.hosts_receiver) => match r {
+                    None => self.hosts_receiver = None,
+                    Some(hosts) => if self.host_provider.update_hosts(hosts, self.server.as_ref()) {
+                        log::info!("ZooKeeper session {} reconnects to rebalance load among servers", self.session_id);
+                        return Err(Error::ConnectionLoss);
+                    },
+                },
                 now = tick.tick() => {
                     if now >= self.last_recv + self.connection_timeout {
                         return Err(Error::ConnectionLoss);
@@ -468,32 +705,92 @@ impl Session {
         Err(Error::ClientClosed)
     }
 
+    /// Schedules probe to next server other than connected one for read-write server.
+    ///
+    /// Delay between probes doubles up to [MAX_RW_PROBE_DELAY] as Java client does.
+    async fn probe_rw_server(&mut self) -> RwProbe {
+        let delay = self.rw_probe_delay;
+        self.rw_probe_delay = (delay * 2).min(MAX_RW_PROBE_DELAY);
+        let candidate = |servers: &VecDeque<ServerAddress>, connected: Option<&ServerAddress>| {
+            servers.iter().position(|server| Some(server) != connected)
+        };
+        let mut i = candidate(&self.servers, self.server.as_ref());
+        if i.is_none() {
+            self.servers = self.host_provider.next_round().await.into();
+            i = candidate(&self.servers, self.server.as_ref());
+        }
+        let server = i.and_then(|i| self.servers.remove(i));
+        let connector = self.connector.clone();
+        let timeout = self.connection_timeout;
+        let session_id = self.session_id;
+        Box::pin(async move {
+            time::sleep(delay).await;
+            let server = server?;
+            match time::timeout(timeout, connector.probe_rw(&server)).await {
+                Ok(Ok(true)) => Some(server),
+                Ok(Ok(false)) => None,
+                Ok(Err(err)) => {
+                    log::debug!("ZooKeeper session {} fails to probe {} due to {}", session_id, server.addr, err);
+                    None
+                },
+                Err(_) => {
+                    log::debug!("ZooKeeper session {} times out to probe {}", session_id, server.addr);
+                    None
+                },
+            }
+        })
+    }
+
+    async fn next_server(&mut self, rounds: &mut usize, deadline: &mut Sleep) -> Result<ServerAddress, Error> {
+        loop {
+            if let Some(server) = self.servers.pop_front() {
+                return Ok(server);
+            } else if *rounds == 0 {
+                return Err(Error::NoHosts);
+            }
+            *rounds -= 1;
+            if let Some(backoff) = self.reconnect_backoff.filter(|_| self.failed_rounds != 0) {
+                let delay = backoff.delay(self.failed_rounds);
+                log::debug!("ZooKeeper session {} backs off {}ms to reconnect", self.session_id, delay.as_millis());
+                select! {
+                    _ = unsafe { Pin::new_unchecked(&mut *deadline) } => return Err(Error::Timeout),
+                    _ = time::sleep(delay) => {},
+                }
+            }
+            self.failed_rounds = self.failed_rounds.saturating_add(1);
+            select! {
+                _ = unsafe { Pin::new_unchecked(&mut *deadline) } => return Err(Error::Timeout),
+                servers = self.host_provider.next_round() => self.servers.extend(servers),
+            }
+            if self.servers.is_empty() && *rounds != 0 && self.reconnect_backoff.is_none() {
+                log::debug!("ZooKeeper session {} got no servers to connect", self.session_id);
+                select! {
+                    _ = unsafe { Pin::new_unchecked(&mut *deadline) } => return Err(Error::Timeout),
+                    _ = time::sleep(self.tick_timeout) => {},
+                }
+            }
+        }
+    }
+
     async fn new_socket(
         &mut self,
-        hosts: &mut impl Iterator<Item = (&str, u16)>,
+        rounds: &mut usize,
         deadline: &mut Sleep,
-    ) -> Result<TcpStream, Error> {
-        loop {
-            let addr = match hosts.next() {
-                None => return Err(Error::NoHosts),
-                Some(addr) => addr,
-            };
-            select! {
-                _ = unsafe { Pin::new_unchecked(deadline) } => return Err(Error::Timeout),
-                _ = time::sleep(self.connection_timeout) => {
-                    log::debug!("ZooKeeper fails to connect to {}:{} in {}ms", addr.0, addr.1, self.connection_timeout.as_millis());
-                    return Err(Error::ConnectionLoss)
-                },
-                r = TcpStream::connect(addr) => {
-                    return match r {
-                        Err(err) => {
-                            log::debug!("ZooKeeper fails to connect to {}:{} due to {}", addr.0, addr.1, err);
-                            Err(Error::ConnectionLoss)
-                        },
-                        Ok(sock) => Ok(sock),
-                    };
+    ) -> Result<(Connection, ServerAddress), Error> {
+        let server = self.next_server(rounds, deadline).await?;
+        select! {
+            _ = unsafe { Pin::new_unchecked(deadline) } => Err(Error::Timeout),
+            _ = time::sleep(self.connection_timeout) => {
+                log::debug!("ZooKeeper fails to connect to {} in {}ms", server.addr, self.connection_timeout.as_millis());
+                Err(Error::ConnectionLoss)
+            },
+            r = self.connector.connect(&server) => match r {
+                Err(err) => {
+                    log::debug!("ZooKeeper fails to connect to {}({}) due to {}", server.addr, server.host, err);
+                    Err(Error::ConnectionLoss)
                 },
-            }
+                Ok(sock) => Ok((sock, server)),
+            },
         }
     }
 
@@ -507,7 +804,7 @@ impl Session {
     fn send_connect(&self, depot: &mut Depot) {
         let request = ConnectRequest {
             protocol_version: 0,
-            last_zxid_seen: 0,
+            last_zxid_seen: self.last_zxid,
             timeout: self.session_timeout.as_millis() as i32,
             session_id: self.session_id.0,
             password: self.session_password.as_slice(),
@@ -517,6 +814,11 @@ impl Session {
         depot.push_operation(Operation::Connect(operation));
     }
 
+    fn send_sasl(token: &[u8], depot: &mut Depot) {
+        let operation = SessionOperation::new(OpCode::Sasl, &SaslRequest { token });
+        depot.push_session(operation);
+    }
+
     fn send_authes(&self, depot: &mut Depot) {
         self.authes.iter().for_each(|auth| {
             let operation = SessionOperation::from(auth.clone());
@@ -526,39 +828,65 @@ impl Session {
 
     async fn start_once(
         &mut self,
-        hosts: &mut impl Iterator<Item = (&str, u16)>,
+        rounds: &mut usize,
         deadline: &mut Sleep,
         buf: &mut Vec<u8>,
         depot: &mut Depot,
-    ) -> Result<TcpStream, Error> {
-        let sock = self.new_socket(hosts, deadline).await?;
+    ) -> Result<Connection, Error> {
+        let (mut sock, server) = self.new_socket(rounds, deadline).await?;
         depot.clear();
         buf.clear();
         self.send_connect(depot);
-        // TODO: Sasl
-        self.send_authes(depot);
-        self.watch_manager.resend_watches(self.last_zxid, depot);
+        // Authes and watches are deferred after sasl authentication completed.
+        if let Some(sasl) = self.sasl.as_ref() {
+            let client = sasl.new_client();
+            Self::send_sasl(&client.initial_response(), depot);
+            self.sasl_client = Some(client);
+        } else {
+            self.send_authes(depot);
+            self.watch_manager.resend_watches(self.last_zxid, depot);
+        }
         self.last_send = Instant::now();
         self.last_recv = self.last_send;
         self.last_ping = None;
-        self.serve_connecting(&sock, buf, depot).await?;
+        if let Err(err) = self.serve_connecting(&mut sock, buf, depot).await {
+            // Server closes connection if it lags behind our last seen zxid, we will try next one.
+            if err == Error::ConnectionLoss && self.session_state == SessionState::Disconnected {
+                log::debug!(
+                    "ZooKeeper session {} fails to connect server {} with last zxid seen {}",
+                    self.session_id,
+                    server.addr,
+                    self.last_zxid
+                );
+            }
+            return Err(err);
+        }
+        self.host_provider.connected(&server);
+        self.server = Some(server);
+        self.failed_rounds = 0;
+        self.rw_probe_delay = MIN_RW_PROBE_DELAY;
         Ok(sock)
     }
 
+    /// Starts session by trying at most given rounds of servers from host provider.
     pub async fn start(
         &mut self,
-        hosts: &mut impl Iterator<Item = (&str, u16)>,
+        mut rounds: usize,
         buf: &mut Vec<u8>,
         depot: &mut Depot,
-    ) -> Result<TcpStream, Error> {
+    ) -> Result<Connection, Error> {
         let session_timeout = if self.session_id.0 == 0 { self.session_timeout } else { self.session_expired_timeout };
         let mut deadline = time::sleep_until(self.last_recv + session_timeout);
-        let mut last_error = match self.start_once(hosts, &mut deadline, buf, depot).await {
+        let mut last_error = match self.start_once(&mut rounds, &mut deadline, buf, depot).await {
             Err(err) => err,
             Ok(sock) => return Ok(sock),
         };
-        while last_error != Error::NoHosts && last_error != Error::Timeout && last_error != Error::SessionExpired {
-            match self.start_once(hosts, &mut deadline, buf, depot).await {
+        while last_error != Error::NoHosts
+            && last_error != Error::Timeout
+            && last_error != Error::SessionExpired
+            && last_error != Error::AuthFailed
+        {
+            match self.start_once(&mut rounds, &mut deadline, buf, depot).await {
                 Err(err) => {
                     last_error = err;
                     continue;
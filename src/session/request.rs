@@ -1,11 +1,14 @@
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
 use bytes::{Buf, BufMut};
 use ignore_result::Ignore;
 use tokio::sync::oneshot;
+use tokio::time::{self, Instant, Sleep};
 
+use super::limiter::RequestPermit;
 use super::types::WatchMode;
 use super::watch::WatchReceiver;
 use crate::error::Error;
@@ -117,83 +120,148 @@ impl ConnectOperation {
 pub struct SessionOperation {
     pub request: MarshalledRequest,
     pub responser: StateResponser,
+    /// Deadline after which operation should not be sent.
+    pub deadline: Option<Instant>,
 }
 
 impl SessionOperation {
     pub fn new(code: OpCode, body: &dyn Record) -> Self {
         let request = MarshalledRequest::new(code, body);
-        Self { request, responser: Default::default() }
+        Self { request, responser: Default::default(), deadline: None }
     }
 
     pub fn new_without_body(code: OpCode) -> Self {
         let header = RequestHeader::with_code(code);
         let request = MarshalledRequest::new_record(&header);
-        Self { request, responser: StateResponser::default() }
+        Self { request, responser: StateResponser::default(), deadline: None }
     }
 
     pub fn new_marshalled(request: MarshalledRequest) -> Self {
-        Self { request, responser: Default::default() }
+        Self { request, responser: Default::default(), deadline: None }
+    }
+
+    pub fn with_deadline(self, deadline: Option<Instant>) -> Self {
+        Self { deadline, ..self }
+    }
+
+    /// Checks whether operation has passed its deadline.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.deadline.map(|deadline| deadline <= now).unwrap_or(false)
     }
 
     pub fn with_responser(self) -> (Self, StateReceiver) {
         let (sender, receiver) = oneshot::channel();
         let request = self.request;
         let code = request.get_code();
-        let operation = Self { request, responser: StateResponser::new(sender) };
-        (operation, StateReceiver { code, receiver })
+        let deadline = self.deadline.map(|deadline| Box::pin(time::sleep_until(deadline)));
+        let permit = self.deadline.map(|_| Arc::new(PermitSlot::default()));
+        let responser = StateResponser(Some(sender), permit.clone());
+        let operation = Self { request, responser, deadline: self.deadline };
+        (operation, StateReceiver { code, receiver, deadline, permit, sending: None })
     }
 }
 
 impl From<MarshalledRequest> for SessionOperation {
     fn from(request: MarshalledRequest) -> Self {
-        SessionOperation { request, responser: StateResponser::none() }
+        SessionOperation { request, responser: StateResponser::none(), deadline: None }
     }
 }
 
 pub struct StateReceiver {
     code: OpCode,
     receiver: oneshot::Receiver<Result<(Vec<u8>, WatchReceiver), Error>>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    permit: Option<Arc<PermitSlot>>,
+    sending: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
 }
 
 impl StateReceiver {
     pub fn new(code: OpCode, receiver: oneshot::Receiver<Result<(Vec<u8>, WatchReceiver), Error>>) -> Self {
-        Self { code, receiver }
+        Self { code, receiver, deadline: None, permit: None, sending: None }
+    }
+
+    /// Defers sending of request to given future, which is driven before waiting for response.
+    pub fn with_sending(self, sending: impl Future<Output = ()> + Send + 'static) -> Self {
+        Self { sending: Some(Box::pin(sending)), ..self }
     }
 }
 
 impl Future for StateReceiver {
     type Output = Result<(Vec<u8>, WatchReceiver), Error>;
 
+    /// Polls for response, fails with [Error::Timeout] after deadline. Timed out request releases
+    /// its permit of outstanding request, and its response, if any, is discarded silently.
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let code = self.code;
-        let receiver = unsafe { self.map_unchecked_mut(|r| &mut r.receiver) };
-        match receiver.poll(cx) {
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(result) => match result {
-                Err(_) => {
-                    Poll::Ready(Err(Error::UnexpectedError(format!("BUG: {} expect response, but got none", code))))
+        let this = self.get_mut();
+        if let Some(sending) = this.sending.as_mut() {
+            if sending.as_mut().poll(cx).is_ready() {
+                this.sending = None;
+            }
+        }
+        if this.sending.is_none() {
+            match Pin::new(&mut this.receiver).poll(cx) {
+                Poll::Pending => {},
+                Poll::Ready(Err(_)) => {
+                    let code = this.code;
+                    return Poll::Ready(Err(Error::UnexpectedError(format!(
+                        "BUG: {} expect response, but got none",
+                        code
+                    ))));
                 },
-                Ok(r) => Poll::Ready(r),
+                Poll::Ready(Ok(r)) => return Poll::Ready(r),
+            }
+        }
+        match this.deadline.as_mut().map(|deadline| deadline.as_mut().poll(cx)) {
+            Some(Poll::Ready(_)) => {
+                this.receiver.close();
+                if let Some(permit) = this.permit.as_ref() {
+                    permit.release();
+                }
+                Poll::Ready(Err(Error::Timeout))
             },
+            _ => Poll::Pending,
         }
     }
 }
 
 type StateSender = oneshot::Sender<Result<(Vec<u8>, WatchReceiver), Error>>;
 
+/// Permit of outstanding request shared with [StateReceiver], so it is released by either
+/// response or deadline.
 #[derive(Default, Debug)]
-pub struct StateResponser(Option<StateSender>);
+struct PermitSlot(Mutex<Option<RequestPermit>>);
+
+impl PermitSlot {
+    fn set(&self, permit: RequestPermit) {
+        *self.0.lock().unwrap() = Some(permit);
+    }
+
+    fn release(&self) {
+        self.0.lock().unwrap().take();
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct StateResponser(Option<StateSender>, Option<Arc<PermitSlot>>);
 
 impl StateResponser {
     pub fn new(sender: oneshot::Sender<Result<(Vec<u8>, WatchReceiver), Error>>) -> Self {
-        StateResponser(Some(sender))
+        StateResponser(Some(sender), None)
     }
 
     pub fn none() -> Self {
-        StateResponser(None)
+        StateResponser(None, None)
+    }
+
+    /// Attaches permit of outstanding request, it is released before response.
+    pub fn set_permit(&mut self, permit: RequestPermit) {
+        self.1.get_or_insert_with(Default::default).set(permit);
     }
 
     pub fn send(mut self, result: Result<(Vec<u8>, WatchReceiver), Error>) -> bool {
+        if let Some(permit) = self.1.take() {
+            permit.release();
+        }
         if let Some(sender) = self.0.take() {
             sender.send(result).ignore();
             return true;
@@ -0,0 +1,268 @@
+use std::fmt::Write as _;
+
+use md5::{Digest, Md5};
+
+use crate::error::Error;
+
+/// Protocol and server name ZooKeeper server uses for DIGEST-MD5 mechanism.
+const DIGEST_URI: &str = "zookeeper/zk-sasl-md5";
+
+/// Options for SASL authentication.
+#[derive(Clone)]
+pub enum SaslOptions {
+    DigestMd5 { username: String, password: String },
+}
+
+impl std::fmt::Debug for SaslOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaslOptions::DigestMd5 { username, .. } => {
+                f.debug_struct("DigestMd5").field("username", username).finish_non_exhaustive()
+            },
+        }
+    }
+}
+
+impl SaslOptions {
+    pub fn new_client(&self) -> SaslClient {
+        match self {
+            SaslOptions::DigestMd5 { username, password } => {
+                SaslClient::DigestMd5(DigestMd5Client::new(username, password, DIGEST_URI))
+            },
+        }
+    }
+}
+
+/// Client side of SASL challenge/response exchange.
+pub enum SaslClient {
+    DigestMd5(DigestMd5Client),
+}
+
+impl SaslClient {
+    pub fn mechanism(&self) -> &'static str {
+        match self {
+            SaslClient::DigestMd5(_) => "DIGEST-MD5",
+        }
+    }
+
+    /// Returns initial token to send to server.
+    pub fn initial_response(&self) -> Vec<u8> {
+        match self {
+            // DIGEST-MD5 has no initial response, an empty token is sent to solicit challenge.
+            SaslClient::DigestMd5(_) => Vec::new(),
+        }
+    }
+
+    /// Evaluates challenge from server and returns response to send back, or `None` if
+    /// authentication completed.
+    pub fn evaluate(&mut self, challenge: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        match self {
+            SaslClient::DigestMd5(client) => client.evaluate(challenge),
+        }
+    }
+}
+
+enum DigestMd5Step {
+    Challenge,
+    ResponseAuth { rspauth: String },
+    Completed,
+}
+
+/// DIGEST-MD5 client as specified in [RFC 2831](https://www.rfc-editor.org/rfc/rfc2831) with
+/// quality of protection `auth`.
+pub struct DigestMd5Client {
+    username: String,
+    password: String,
+    digest_uri: &'static str,
+    step: DigestMd5Step,
+}
+
+impl DigestMd5Client {
+    fn new(username: &str, password: &str, digest_uri: &'static str) -> Self {
+        Self {
+            username: username.to_string(),
+            password: password.to_string(),
+            digest_uri,
+            step: DigestMd5Step::Challenge,
+        }
+    }
+
+    fn evaluate(&mut self, challenge: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        match std::mem::replace(&mut self.step, DigestMd5Step::Completed) {
+            DigestMd5Step::Challenge => {
+                let cnonce = uuid::Uuid::new_v4().simple().to_string();
+                let (response, rspauth) = self.respond(challenge, &cnonce)?;
+                self.step = DigestMd5Step::ResponseAuth { rspauth };
+                Ok(Some(response.into_bytes()))
+            },
+            DigestMd5Step::ResponseAuth { rspauth } => {
+                let directives = parse_directives(challenge)?;
+                match find_directive(&directives, "rspauth") {
+                    Some(value) if value == rspauth => Ok(None),
+                    _ => Err(Error::AuthFailed),
+                }
+            },
+            DigestMd5Step::Completed => {
+                Err(Error::UnexpectedError("DIGEST-MD5 challenge after completion".to_string()))
+            },
+        }
+    }
+
+    /// Computes response to digest challenge together with expected `rspauth` from server.
+    fn respond(&self, challenge: &[u8], cnonce: &str) -> Result<(String, String), Error> {
+        let directives = parse_directives(challenge)?;
+        let nonce = find_directive(&directives, "nonce").ok_or(Error::AuthFailed)?;
+        let realm = find_directive(&directives, "realm").unwrap_or_default();
+        let algorithm = find_directive(&directives, "algorithm");
+        if algorithm.as_deref() != Some("md5-sess") {
+            log::warn!("ZooKeeper DIGEST-MD5 challenge has unsupported algorithm {:?}", algorithm);
+            return Err(Error::AuthFailed);
+        }
+        if let Some(qop) = find_directive(&directives, "qop") {
+            if !qop.split(',').any(|qop| qop.trim() == "auth") {
+                log::warn!("ZooKeeper DIGEST-MD5 challenge has unsupported qop {}", qop);
+                return Err(Error::AuthFailed);
+            }
+        }
+        let nc = "00000001";
+        let digest = |a2_prefix: &str| {
+            let mut hasher = Md5::new();
+            hasher.update(self.username.as_bytes());
+            hasher.update(b":");
+            hasher.update(realm.as_bytes());
+            hasher.update(b":");
+            hasher.update(self.password.as_bytes());
+            let user_hash = hasher.finalize();
+            let mut hasher = Md5::new();
+            hasher.update(user_hash);
+            hasher.update(format!(":{}:{}", nonce, cnonce).as_bytes());
+            let ha1 = hex(&hasher.finalize());
+            let ha2 = hex(&Md5::digest(format!("{}:{}", a2_prefix, self.digest_uri).as_bytes()));
+            hex(&Md5::digest(format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2).as_bytes()))
+        };
+        let mut response = String::with_capacity(256);
+        response.push_str("charset=utf-8,username=");
+        quote(&mut response, &self.username);
+        if !realm.is_empty() {
+            response.push_str(",realm=");
+            quote(&mut response, &realm);
+        }
+        response.push_str(",nonce=");
+        quote(&mut response, &nonce);
+        write!(&mut response, ",nc={},cnonce=", nc).unwrap();
+        quote(&mut response, cnonce);
+        response.push_str(",digest-uri=");
+        quote(&mut response, self.digest_uri);
+        write!(&mut response, ",maxbuf=65536,response={},qop=auth", digest("AUTHENTICATE")).unwrap();
+        Ok((response, digest("")))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 * bytes.len());
+    bytes.iter().for_each(|b| write!(&mut s, "{:02x}", b).unwrap());
+    s
+}
+
+fn quote(buf: &mut String, value: &str) {
+    buf.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            buf.push('\\');
+        }
+        buf.push(c);
+    }
+    buf.push('"');
+}
+
+fn find_directive(directives: &[(String, String)], name: &str) -> Option<String> {
+    directives.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.clone())
+}
+
+/// Parses comma separated `key=value` or `key="quoted value"` directives.
+fn parse_directives(challenge: &[u8]) -> Result<Vec<(String, String)>, Error> {
+    let invalid =
+        || Error::UnexpectedError(format!("invalid DIGEST-MD5 challenge {}", String::from_utf8_lossy(challenge)));
+    let challenge = std::str::from_utf8(challenge).map_err(|_| invalid())?;
+    let mut directives = Vec::new();
+    let mut chars = challenge.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=') {
+            key.push(c);
+        }
+        if chars.next() != Some('=') {
+            return Err(invalid());
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    None => return Err(invalid()),
+                    Some('"') => break,
+                    Some('\\') => value.push(chars.next().ok_or_else(invalid)?),
+                    Some(c) => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+        }
+        directives.push((key.trim().to_string(), value.trim().to_string()));
+    }
+    Ok(directives)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_directives() {
+        let challenge = br#"realm="zk-sasl-md5",nonce="a\"b",qop="auth,auth-int", charset=utf-8,algorithm=md5-sess"#;
+        assert_eq!(parse_directives(challenge).unwrap(), vec![
+            ("realm".to_string(), "zk-sasl-md5".to_string()),
+            ("nonce".to_string(), "a\"b".to_string()),
+            ("qop".to_string(), "auth,auth-int".to_string()),
+            ("charset".to_string(), "utf-8".to_string()),
+            ("algorithm".to_string(), "md5-sess".to_string()),
+        ]);
+        parse_directives(br#"nonce="abc"#).unwrap_err();
+        parse_directives(b"nonce").unwrap_err();
+    }
+
+    #[test]
+    fn test_digest_md5_rfc2831() {
+        let client = DigestMd5Client::new("chris", "secret", "imap/elwood.innosoft.com");
+        let challenge =
+            br#"realm="elwood.innosoft.com",nonce="OA6MG9tEQGm2hh",qop="auth",algorithm=md5-sess,charset=utf-8"#;
+        let (response, rspauth) = client.respond(challenge, "OA6MHXh6VqTrRk").unwrap();
+        assert_eq!(
+            response,
+            r#"charset=utf-8,username="chris",realm="elwood.innosoft.com",nonce="OA6MG9tEQGm2hh",nc=00000001,cnonce="OA6MHXh6VqTrRk",digest-uri="imap/elwood.innosoft.com",maxbuf=65536,response=d388dad90d4bbd760a152321f2143af7,qop=auth"#
+        );
+        assert_eq!(rspauth, "ea40f60335c427b5527b84dbabcdfffd");
+    }
+
+    #[test]
+    fn test_digest_md5_exchange() {
+        let mut client =
+            SaslOptions::DigestMd5 { username: "user".to_string(), password: "pass".to_string() }.new_client();
+        assert_eq!(client.initial_response(), Vec::<u8>::new());
+        let challenge = br#"realm="zk-sasl-md5",nonce="abc",qop="auth",charset=utf-8,algorithm=md5-sess"#;
+        client.evaluate(challenge).unwrap().unwrap();
+        assert_eq!(client.evaluate(b"rspauth=0123").unwrap_err(), Error::AuthFailed);
+
+        let mut client =
+            SaslOptions::DigestMd5 { username: "user".to_string(), password: "pass".to_string() }.new_client();
+        let challenge = br#"realm="zk-sasl-md5",nonce="abc",qop="auth-conf",charset=utf-8,algorithm=md5-sess"#;
+        assert_eq!(client.evaluate(challenge).unwrap_err(), Error::AuthFailed);
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use strum::EnumIter;
 
@@ -15,6 +17,14 @@ impl std::fmt::Display for SessionId {
     }
 }
 
+/// Identity and negotiated timeout of established session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub id: SessionId,
+    pub password: Vec<u8>,
+    pub timeout: Duration,
+}
+
 /// ZooKeeper session states.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, strum::Display)]
 pub enum SessionState {
@@ -35,6 +45,11 @@ pub enum SessionState {
 
     /// Terminal state states that zookeeper client has been closed.
     Closed,
+
+    /// Intermediate state states that client has established a new session after previous one
+    /// expired. The new session could be connected to readonly server if allowed. See
+    /// [crate::ClientBuilder::with_session_recreation].
+    Reestablished,
 }
 
 impl SessionState {
@@ -52,7 +67,8 @@ impl SessionState {
     }
 
     pub(crate) fn is_connected(self) -> bool {
-        self == SessionState::SyncConnected || self == SessionState::ConnectedReadOnly
+        use SessionState::*;
+        matches!(self, SyncConnected | ConnectedReadOnly | Reestablished)
     }
 
     pub(crate) fn to_error(self) -> Error {
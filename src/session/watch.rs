@@ -326,6 +326,7 @@ impl WatchManager {
         });
         if event.session_state.is_terminated() {
             self.watches.clear();
+            self.watching_paths.clear();
         }
     }
 
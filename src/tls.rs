@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use rustls_pemfile::Item;
+
+use crate::error::Error;
+
+/// Options for tls connection to ZooKeeper servers.
+#[derive(Clone)]
+pub struct TlsOptions {
+    ca_certs: RootCertStore,
+    identity: Option<(Vec<Certificate>, PrivateKey)>,
+    hostname_verification: bool,
+}
+
+impl std::fmt::Debug for TlsOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsOptions")
+            .field("ca_certs", &self.ca_certs.len())
+            .field("identity", &self.identity.is_some())
+            .field("hostname_verification", &self.hostname_verification)
+            .finish()
+    }
+}
+
+impl Default for TlsOptions {
+    /// Constructs options with no trusted certificates and hostname verification enabled.
+    fn default() -> Self {
+        Self { ca_certs: RootCertStore::empty(), identity: None, hostname_verification: true }
+    }
+}
+
+impl TlsOptions {
+    /// Trusts certificates in given PEM to verify servers.
+    pub fn with_pem_ca_certs(mut self, certs: &str) -> Result<Self, Error> {
+        let certs = rustls_pemfile::certs(&mut certs.as_bytes())
+            .map_err(|_| Error::BadArguments(&"malformed pem ca certificates"))?;
+        if certs.is_empty() {
+            return Err(Error::BadArguments(&"no ca certificates in pem"));
+        }
+        for cert in certs.into_iter().map(Certificate) {
+            self.ca_certs.add(&cert).map_err(|_| Error::BadArguments(&"invalid ca certificate"))?;
+        }
+        Ok(self)
+    }
+
+    /// Presents given PEM certificate chain and private key to servers for x509 authentication.
+    pub fn with_pem_identity(mut self, cert: &str, key: &str) -> Result<Self, Error> {
+        let certs = rustls_pemfile::certs(&mut cert.as_bytes())
+            .map_err(|_| Error::BadArguments(&"malformed pem client certificate"))?;
+        if certs.is_empty() {
+            return Err(Error::BadArguments(&"no client certificate in pem"));
+        }
+        let mut reader = key.as_bytes();
+        let key = loop {
+            match rustls_pemfile::read_one(&mut reader) {
+                Err(_) => return Err(Error::BadArguments(&"malformed pem client private key")),
+                Ok(None) => return Err(Error::BadArguments(&"no client private key in pem")),
+                Ok(Some(Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key))) => break PrivateKey(key),
+                Ok(Some(_)) => continue,
+            }
+        };
+        self.identity = Some((certs.into_iter().map(Certificate).collect(), key));
+        Ok(self)
+    }
+
+    /// Specifies whether to verify that server certificate matches connecting hostname.
+    ///
+    /// Defaults to `true`.
+    pub fn with_hostname_verification(mut self, verify: bool) -> Self {
+        self.hostname_verification = verify;
+        self
+    }
+
+    pub(crate) fn into_config(self) -> Result<ClientConfig, Error> {
+        let verifier = WebPkiVerifier::new(self.ca_certs, None);
+        let verifier: Arc<dyn ServerCertVerifier> =
+            if self.hostname_verification { Arc::new(verifier) } else { Arc::new(NoHostnameVerifier(verifier)) };
+        let builder = ClientConfig::builder().with_safe_defaults().with_custom_certificate_verifier(verifier);
+        match self.identity {
+            None => Ok(builder.with_no_client_auth()),
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|_| Error::BadArguments(&"invalid client certificate or private key")),
+        }
+    }
+}
+
+/// Verifies server certificate chain but not its hostname.
+struct NoHostnameVerifier(WebPkiVerifier);
+
+impl ServerCertVerifier for NoHostnameVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match self.0.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now) {
+            Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName)) => {
+                Ok(ServerCertVerified::assertion())
+            },
+            result => result,
+        }
+    }
+}
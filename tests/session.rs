@@ -0,0 +1,631 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use md5::{Digest, Md5};
+use pretty_assertions::assert_eq;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::select;
+use tokio::sync::Notify;
+use zookeeper_client as zk;
+
+#[derive(Default)]
+struct StubState {
+    zxid: i64,
+    session_id: i64,
+    last_zxids_seen: Vec<i64>,
+    config: Option<String>,
+    readonly: bool,
+    delay: Duration,
+    sasl: Option<(String, String)>,
+    op_codes: Vec<i32>,
+}
+
+/// ZooKeeper server stand-in which has no nodes except optional ensemble config but tracks zxid.
+#[derive(Clone)]
+struct StubServer {
+    port: u16,
+    state: Arc<Mutex<StubState>>,
+    kick: Arc<Notify>,
+}
+
+impl StubServer {
+    async fn start(zxid: i64) -> StubServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(StubState { zxid, session_id: 1, ..Default::default() }));
+        let server = StubServer { port, state, kick: Arc::new(Notify::new()) };
+        let serving = server.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serving.clone().serve(stream));
+            }
+        });
+        server
+    }
+
+    fn address(&self) -> String {
+        format!("127.0.0.1:{}", self.port)
+    }
+
+    fn set_zxid(&self, zxid: i64) {
+        self.state.lock().unwrap().zxid = zxid;
+    }
+
+    fn set_config(&self, config: String) {
+        self.state.lock().unwrap().config = Some(config);
+    }
+
+    fn set_readonly(&self, readonly: bool) {
+        self.state.lock().unwrap().readonly = readonly;
+    }
+
+    /// Requires DIGEST-MD5 authentication with given credentials.
+    fn set_sasl(&self, username: &str, password: &str) {
+        self.state.lock().unwrap().sasl = Some((username.to_string(), password.to_string()));
+    }
+
+    /// Op codes of received requests other than ping.
+    fn op_codes(&self) -> Vec<i32> {
+        self.state.lock().unwrap().op_codes.clone()
+    }
+
+    /// Delays responses to requests other than ping.
+    fn set_delay(&self, delay: Duration) {
+        self.state.lock().unwrap().delay = delay;
+    }
+
+    /// Expires current session and closes all established connections.
+    fn expire(&self) {
+        self.state.lock().unwrap().session_id += 1;
+        self.kick();
+    }
+
+    fn last_zxids_seen(&self) -> Vec<i64> {
+        self.state.lock().unwrap().last_zxids_seen.clone()
+    }
+
+    /// Closes all established connections.
+    fn kick(&self) {
+        self.kick.notify_waiters();
+    }
+
+    async fn read_frame(stream: &mut TcpStream) -> Option<Vec<u8>> {
+        let len = stream.read_i32().await.ok()?;
+        let mut buf = vec![0; len as usize];
+        stream.read_exact(&mut buf).await.ok()?;
+        Some(buf)
+    }
+
+    fn request_buffer(body: &[u8]) -> &[u8] {
+        let len = i32::from_be_bytes(body[0..4].try_into().unwrap()).max(0) as usize;
+        &body[4..4 + len]
+    }
+
+    fn request_path(body: &[u8]) -> &str {
+        std::str::from_utf8(Self::request_buffer(body)).unwrap()
+    }
+
+    /// Serves one step of DIGEST-MD5 exchange, returns `None` if client fails authentication.
+    fn authenticate(credentials: Option<&(String, String)>, token: &[u8]) -> Option<String> {
+        let (username, password) = credentials?;
+        if token.is_empty() {
+            return Some(
+                r#"realm="zk-sasl-md5",nonce="stub-nonce",qop="auth",charset=utf-8,algorithm=md5-sess"#.to_string(),
+            );
+        }
+        let directives: HashMap<&str, &str> = std::str::from_utf8(token)
+            .unwrap()
+            .split(',')
+            .filter_map(|directive| directive.split_once('='))
+            .map(|(key, value)| (key, value.trim_matches('"')))
+            .collect();
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        let digest = |a2_prefix: &str| {
+            let mut hasher = Md5::new();
+            hasher.update(Md5::digest(format!("{}:{}:{}", username, directives["realm"], password)));
+            hasher.update(format!(":{}:{}", directives["nonce"], directives["cnonce"]));
+            let ha1 = hex(hasher.finalize().as_slice());
+            let ha2 = hex(Md5::digest(format!("{}:{}", a2_prefix, directives["digest-uri"])).as_slice());
+            let (nonce, nc, cnonce) = (directives["nonce"], directives["nc"], directives["cnonce"]);
+            hex(Md5::digest(format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2)).as_slice())
+        };
+        if directives.get("username") != Some(&username.as_str()) || directives["response"] != digest("AUTHENTICATE") {
+            return None;
+        }
+        Some(format!("rspauth={}", digest("")))
+    }
+
+    async fn serve(self, mut stream: TcpStream) {
+        let mut len = [0; 4];
+        if stream.read_exact(&mut len).await.is_err() {
+            return;
+        }
+        if &len == b"isro" {
+            let readonly = self.state.lock().unwrap().readonly;
+            stream.write_all(if readonly { b"ro" } else { b"rw" }).await.unwrap();
+            return;
+        }
+        let mut request = vec![0; i32::from_be_bytes(len) as usize];
+        if stream.read_exact(&mut request).await.is_err() {
+            return;
+        }
+        let last_zxid_seen = i64::from_be_bytes(request[4..12].try_into().unwrap());
+        let timeout = i32::from_be_bytes(request[12..16].try_into().unwrap());
+        let requested_session_id = i64::from_be_bytes(request[16..24].try_into().unwrap());
+        let (zxid, readonly, session_id) = {
+            let mut state = self.state.lock().unwrap();
+            state.last_zxids_seen.push(last_zxid_seen);
+            (state.zxid, state.readonly, state.session_id)
+        };
+        if last_zxid_seen > zxid {
+            // Refuses client which has seen newer state as ZooKeeper does.
+            return;
+        }
+        // Session id 0 states that requested session has expired.
+        let session_id = if requested_session_id == 0 || requested_session_id == session_id { session_id } else { 0 };
+        let mut response = vec![];
+        response.extend_from_slice(&0i32.to_be_bytes());
+        response.extend_from_slice(&timeout.to_be_bytes());
+        response.extend_from_slice(&session_id.to_be_bytes());
+        response.extend_from_slice(&16i32.to_be_bytes());
+        response.extend_from_slice(&[0; 16]);
+        response.push(readonly as u8);
+        stream.write_i32(response.len() as i32).await.unwrap();
+        stream.write_all(&response).await.unwrap();
+        loop {
+            let request = select! {
+                _ = self.kick.notified() => return,
+                request = Self::read_frame(&mut stream) => match request {
+                    None => return,
+                    Some(request) => request,
+                },
+            };
+            let xid = i32::from_be_bytes(request[0..4].try_into().unwrap());
+            let op_code = i32::from_be_bytes(request[4..8].try_into().unwrap());
+            let (zxid, config, delay, sasl) = {
+                let mut state = self.state.lock().unwrap();
+                if op_code != 11 {
+                    state.op_codes.push(op_code);
+                }
+                (state.zxid, state.config.clone(), state.delay, state.sasl.clone())
+            };
+            if op_code != 11 && !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            let mut body = vec![];
+            // Not found for all requests except ping, close, auth, set watches, sasl and get config.
+            let err = match (op_code, config) {
+                (11 | -11 | 100 | 101 | 105, _) => 0,
+                (102, _) => match Self::authenticate(sasl.as_ref(), Self::request_buffer(&request[8..])) {
+                    None => -115,
+                    Some(challenge) => {
+                        body.extend_from_slice(&(challenge.len() as i32).to_be_bytes());
+                        body.extend_from_slice(challenge.as_bytes());
+                        0
+                    },
+                },
+                (4, Some(config)) if Self::request_path(&request[8..]) == "/zookeeper/config" => {
+                    body.extend_from_slice(&(config.len() as i32).to_be_bytes());
+                    body.extend_from_slice(config.as_bytes());
+                    let mut stat = [0; 68];
+                    stat[52..56].copy_from_slice(&(config.len() as i32).to_be_bytes());
+                    body.extend_from_slice(&stat);
+                    0
+                },
+                _ => -101,
+            };
+            stream.write_i32(16 + body.len() as i32).await.unwrap();
+            stream.write_i32(xid).await.unwrap();
+            stream.write_i64(zxid).await.unwrap();
+            stream.write_i32(err).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+            // Server closes connection after authentication failure.
+            if op_code == -11 || err == -115 {
+                return;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_last_zxid_seen() {
+    let server1 = StubServer::start(100).await;
+    let server2 = StubServer::start(100).await;
+    let cluster = format!("{},{}", server1.address(), server2.address());
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let mut state_watcher = client.state_watcher();
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+
+    let (first, second) = if server1.last_zxids_seen().is_empty() { (server2, server1) } else { (server1, server2) };
+    assert_eq!(first.last_zxids_seen(), vec![0]);
+
+    // First server now lags behind client and will refuse it.
+    first.set_zxid(50);
+    second.set_zxid(200);
+    first.kick();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+
+    assert_eq!(second.last_zxids_seen(), vec![100]);
+    assert!(first.last_zxids_seen()[1..].iter().all(|zxid| *zxid == 100));
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_sasl_digest() {
+    let server = StubServer::start(100).await;
+    server.set_sasl("bob", "secret");
+    let client = zk::Client::builder()
+        .with_sasl_digest("bob".to_string(), "secret".to_string())
+        .with_auth("digest".to_string(), b"bob:xyz".to_vec())
+        .connect(&server.address())
+        .await
+        .unwrap();
+    let (_, _watcher) = client.check_and_watch_stat("/a").await.unwrap();
+
+    // Auth packets are sent after sasl exchange of solicitation and digest response.
+    assert_eq!(server.op_codes(), vec![102, 102, 100, 3]);
+
+    // So are watches after reconnection.
+    let mut state_watcher = client.state_watcher();
+    server.kick();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+    assert_eq!(&server.op_codes()[4..], &[102, 102, 100, 101, 3]);
+}
+
+#[tokio::test]
+async fn test_sasl_digest_rejected() {
+    let server = StubServer::start(100).await;
+    server.set_sasl("bob", "secret");
+    let connecting =
+        zk::Client::builder().with_sasl_digest("bob".to_string(), "xyz".to_string()).connect(&server.address());
+    assert_eq!(connecting.await.unwrap_err(), zk::Error::AuthFailed);
+
+    let client = zk::Client::builder()
+        .with_sasl_digest("bob".to_string(), "secret".to_string())
+        .connect(&server.address())
+        .await
+        .unwrap();
+    let mut state_watcher = client.state_watcher();
+
+    // Rejection in reconnection terminates session.
+    server.set_sasl("bob", "changed");
+    server.kick();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    let pending = client.check_stat("/a");
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while state_watcher.changed().await != zk::SessionState::AuthFailed {}
+    })
+    .await
+    .unwrap();
+    assert_eq!(pending.await.unwrap_err(), zk::Error::AuthFailed);
+    assert_eq!(client.check_stat("/a").await.unwrap_err(), zk::Error::AuthFailed);
+}
+
+#[derive(Clone, Default)]
+struct RecordingHostProvider {
+    servers: Arc<Mutex<Vec<zk::ServerAddress>>>,
+    rounds: Arc<Mutex<usize>>,
+    connected: Arc<Mutex<Vec<zk::ServerAddress>>>,
+}
+
+impl zk::HostProvider for RecordingHostProvider {
+    fn next_round(&mut self) -> Pin<Box<dyn Future<Output = Vec<zk::ServerAddress>> + Send + '_>> {
+        *self.rounds.lock().unwrap() += 1;
+        let servers = self.servers.lock().unwrap().clone();
+        Box::pin(async move { servers })
+    }
+
+    fn connected(&mut self, server: &zk::ServerAddress) {
+        self.connected.lock().unwrap().push(server.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_host_provider() {
+    let server = StubServer::start(100).await;
+    let address = zk::ServerAddress { host: "localhost".to_string(), addr: server.address().parse().unwrap() };
+    let provider = RecordingHostProvider::default();
+    provider.servers.lock().unwrap().push(address.clone());
+
+    // Hosts in connect string are up to host provider.
+    let factory_provider = provider.clone();
+    let client = zk::Client::builder()
+        .with_host_provider(move |hosts| {
+            assert_eq!(hosts, vec![("unresolvable.invalid".to_string(), 2181)]);
+            factory_provider.clone()
+        })
+        .connect("unresolvable.invalid:2181")
+        .await
+        .unwrap();
+    assert_eq!(*provider.rounds.lock().unwrap(), 1);
+    assert_eq!(*provider.connected.lock().unwrap(), vec![address.clone()]);
+
+    let mut state_watcher = client.state_watcher();
+    server.kick();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+    assert_eq!(*provider.rounds.lock().unwrap(), 2);
+    assert_eq!(*provider.connected.lock().unwrap(), vec![address.clone(), address]);
+}
+
+#[tokio::test]
+async fn test_reconnect_backoff() {
+    let server = StubServer::start(100).await;
+    let address = zk::ServerAddress { host: "localhost".to_string(), addr: server.address().parse().unwrap() };
+    let dead_address = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        zk::ServerAddress { host: "localhost".to_string(), addr: listener.local_addr().unwrap() }
+    };
+    let provider = RecordingHostProvider::default();
+    provider.servers.lock().unwrap().push(address.clone());
+
+    let factory_provider = provider.clone();
+    let backoff = zk::ReconnectBackoff::new(Duration::from_millis(200), Duration::from_millis(400)).with_jitter(0.0);
+    let client = zk::Client::builder()
+        .with_host_provider(move |_| factory_provider.clone())
+        .with_reconnect_backoff(backoff)
+        .connect("localhost:2181")
+        .await
+        .unwrap();
+    assert_eq!(*provider.rounds.lock().unwrap(), 1);
+
+    // Rounds after first failed one are delayed by 200ms, 400ms, 400ms and so on.
+    *provider.servers.lock().unwrap() = vec![dead_address];
+    let mut state_watcher = client.state_watcher();
+    server.kick();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    assert_eq!(*provider.rounds.lock().unwrap(), 1 + 3);
+
+    *provider.servers.lock().unwrap() = vec![address];
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+    assert_eq!(*provider.rounds.lock().unwrap(), 1 + 4);
+}
+
+#[tokio::test]
+async fn test_server_tracking() {
+    let server1 = StubServer::start(100).await;
+    let server2 = StubServer::start(100).await;
+    server1.set_config(format!("server.2=127.0.0.1:2888:3888:participant;{}\nversion=100", server2.address()));
+
+    // Client migrates to server2 as server1 is no longer part of ensemble.
+    let client = zk::Client::builder().with_server_tracking(true).connect(&server1.address()).await.unwrap();
+    let mut state_watcher = client.state_watcher();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+    assert_eq!(server2.last_zxids_seen().len(), 1);
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_server_tracking_after_session_recreation() {
+    let server1 = StubServer::start(100).await;
+    let server2 = StubServer::start(100).await;
+    server1.set_config(format!("server.1=127.0.0.1:2888:3888:participant;{}\nversion=100", server1.address()));
+    let client = zk::Client::builder()
+        .with_server_tracking(true)
+        .with_session_recreation(true)
+        .connect(&server1.address())
+        .await
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !server1.op_codes().contains(&4) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    // Stub server sends no notification, so config change is only observed by recreated session.
+    server1.set_config(format!("server.2=127.0.0.1:2888:3888:participant;{}\nversion=200", server2.address()));
+    // Recreated session is known to server2.
+    server2.expire();
+    server1.expire();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while server2.last_zxids_seen().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+    assert_eq!(client.session_id(), zk::SessionId(2));
+    assert_eq!(client.state(), zk::SessionState::SyncConnected);
+}
+
+#[tokio::test]
+async fn test_readonly_migration() {
+    let server1 = StubServer::start(100).await;
+    let server2 = StubServer::start(100).await;
+    server1.set_readonly(true);
+    server2.set_readonly(true);
+    let provider = RecordingHostProvider::default();
+    for server in [&server1, &server2] {
+        let address = zk::ServerAddress { host: "localhost".to_string(), addr: server.address().parse().unwrap() };
+        provider.servers.lock().unwrap().push(address);
+    }
+
+    let factory_provider = provider.clone();
+    let client = zk::Client::builder()
+        .with_host_provider(move |_| factory_provider.clone())
+        .with_readonly(true)
+        .connect("localhost:2181")
+        .await
+        .unwrap();
+    let mut state_watcher = client.state_watcher();
+    assert_eq!(state_watcher.state(), zk::SessionState::ConnectedReadOnly);
+    assert_eq!(server2.last_zxids_seen(), Vec::<i64>::new());
+
+    // Session migrates to server2 once it turns read-write, after in-flight requests drained.
+    server1.set_delay(Duration::from_millis(300));
+    let pending = client.check_stat("/a");
+    server2.set_readonly(false);
+    assert_eq!(pending.await.unwrap(), None);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+    assert_eq!(server2.last_zxids_seen().len(), 1);
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_request_timeout() {
+    let server = StubServer::start(100).await;
+    let client = zk::Client::builder()
+        .with_request_timeout(Duration::from_millis(100))
+        .connect(&server.address())
+        .await
+        .unwrap();
+    assert_eq!(client.request_timeout(), Duration::from_millis(100));
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+
+    server.set_delay(Duration::from_millis(500));
+    assert_eq!(client.check_stat("/a").await.unwrap_err(), zk::Error::Timeout);
+
+    // Late response of timed out request is discarded.
+    let client = client.with_timeout(Duration::ZERO);
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+    assert_eq!(client.state(), zk::SessionState::SyncConnected);
+
+    let result = client.with_timeout(Duration::from_millis(100)).get_data("/a").await;
+    assert_eq!(result.unwrap_err(), zk::Error::Timeout);
+}
+
+#[tokio::test]
+async fn test_max_outstanding_requests() {
+    let server = StubServer::start(100).await;
+    server.set_delay(Duration::from_millis(200));
+
+    // Fail fast.
+    let client = zk::Client::builder()
+        .with_max_outstanding_requests(1)
+        .with_fail_fast(true)
+        .connect(&server.address())
+        .await
+        .unwrap();
+    let first = client.check_stat("/a");
+    assert_eq!(client.outstanding_requests(), 1);
+    assert_eq!(client.check_stat("/b").await.unwrap_err(), zk::Error::TooManyRequests);
+    assert_eq!(first.await.unwrap(), None);
+    assert_eq!(client.outstanding_requests(), 0);
+
+    // Backpressure.
+    let client = zk::Client::builder().with_max_outstanding_requests(1).connect(&server.address()).await.unwrap();
+    let first = client.check_stat("/a");
+    let second = client.check_stat("/b");
+    assert_eq!(client.outstanding_requests(), 1);
+    let (first, second) = tokio::join!(first, second);
+    assert_eq!(first.unwrap(), None);
+    assert_eq!(second.unwrap(), None);
+    assert_eq!(client.outstanding_requests(), 0);
+
+    // Request waiting for permit times out.
+    let first = client.check_stat("/a");
+    let second = client.with_timeout(Duration::from_millis(50)).check_stat("/b");
+    assert_eq!(second.await.unwrap_err(), zk::Error::Timeout);
+    assert_eq!(first.await.unwrap(), None);
+    assert_eq!(client.outstanding_requests(), 0);
+
+    // Timed out request releases its permit without waiting for response.
+    let timed_out = client.with_timeout(Duration::from_millis(50)).check_stat("/a");
+    assert_eq!(client.outstanding_requests(), 1);
+    assert_eq!(timed_out.await.unwrap_err(), zk::Error::Timeout);
+    assert_eq!(client.outstanding_requests(), 0);
+    assert_eq!(client.check_stat("/b").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_session_recreation() {
+    let server = StubServer::start(100).await;
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let client = zk::Client::builder()
+        .with_reinit_hook(move |client: zk::Client| {
+            let sender = sender.clone();
+            async move {
+                sender.send((client.session_id(), client.check_stat("/a").await)).unwrap();
+            }
+        })
+        .connect(&server.address())
+        .await
+        .unwrap();
+    let cloned = client.clone();
+    let mut state_watcher = client.state_watcher();
+    let (_, watcher) = client.check_and_watch_stat("/a").await.unwrap();
+    assert_eq!(client.session_id(), zk::SessionId(1));
+
+    server.expire();
+    let event = watcher.changed().await;
+    assert_eq!(event.event_type, zk::EventType::Session);
+    assert_eq!(event.session_state, zk::SessionState::Expired);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while state_watcher.changed().await != zk::SessionState::Reestablished {}
+    })
+    .await
+    .unwrap();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+    assert_eq!(client.state(), zk::SessionState::SyncConnected);
+    assert_eq!(receiver.recv().await.unwrap(), (zk::SessionId(2), Ok(None)));
+    assert_eq!(client.session_id(), zk::SessionId(2));
+    assert_eq!(cloned.session_id(), zk::SessionId(2));
+    assert_eq!(cloned.check_stat("/a").await.unwrap(), None);
+
+    // Watcher created after reestablishment observes no change.
+    let mut fresh_watcher = client.state_watcher();
+    assert_eq!(fresh_watcher.peek_state(), zk::SessionState::SyncConnected);
+    tokio::time::timeout(Duration::from_millis(100), fresh_watcher.changed()).await.unwrap_err();
+
+    // Reconnection to same session is not reestablishment.
+    server.kick();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::SyncConnected);
+    assert_eq!(client.session_id(), zk::SessionId(2));
+    assert!(receiver.try_recv().is_err());
+
+    // Hooks do not keep session alive.
+    drop(client);
+    drop(cloned);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Closed);
+}
+
+#[tokio::test]
+async fn test_session_recreation_readonly() {
+    let server = StubServer::start(100).await;
+    server.set_readonly(true);
+    let client = zk::Client::builder()
+        .with_readonly(true)
+        .with_session_recreation(true)
+        .connect(&server.address())
+        .await
+        .unwrap();
+    let mut state_watcher = client.state_watcher();
+    assert_eq!(state_watcher.state(), zk::SessionState::ConnectedReadOnly);
+
+    server.expire();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while state_watcher.changed().await != zk::SessionState::Reestablished {}
+    })
+    .await
+    .unwrap();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::ConnectedReadOnly);
+    assert_eq!(client.state(), zk::SessionState::ConnectedReadOnly);
+    assert_eq!(client.session_id(), zk::SessionId(2));
+}
+
+#[tokio::test]
+async fn test_session_expiration_without_recreation() {
+    let server = StubServer::start(100).await;
+    let client = zk::Client::connect(&server.address()).await.unwrap();
+    let mut state_watcher = client.state_watcher();
+    server.expire();
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Disconnected);
+    assert_eq!(state_watcher.changed().await, zk::SessionState::Expired);
+    assert_eq!(client.check_stat("/a").await.unwrap_err(), zk::Error::SessionExpired);
+}
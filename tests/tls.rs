@@ -0,0 +1,172 @@
+#![cfg(feature = "tls")]
+
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use pretty_assertions::assert_eq;
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{self, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use zookeeper_client as zk;
+
+struct Pki {
+    ca: Certificate,
+}
+
+impl Pki {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(vec![]);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        Self { ca: Certificate::from_params(params).unwrap() }
+    }
+
+    fn ca_pem(&self) -> String {
+        self.ca.serialize_pem().unwrap()
+    }
+
+    fn issue(&self, names: &[&str]) -> Certificate {
+        let names: Vec<_> = names.iter().map(|name| name.to_string()).collect();
+        Certificate::from_params(CertificateParams::new(names)).unwrap()
+    }
+
+    fn issue_pem(&self, names: &[&str]) -> (String, String) {
+        let cert = self.issue(names);
+        (cert.serialize_pem_with_signer(&self.ca).unwrap(), cert.serialize_private_key_pem())
+    }
+
+    fn roots(&self) -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(&rustls::Certificate(self.ca.serialize_der().unwrap())).unwrap();
+        roots
+    }
+}
+
+fn server_config(pki: &Pki, names: &[&str], client_auth: bool) -> ServerConfig {
+    let cert = pki.issue(names);
+    let certs = vec![rustls::Certificate(cert.serialize_der_with_signer(&pki.ca).unwrap())];
+    let key = rustls::PrivateKey(cert.serialize_private_key_der());
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = if client_auth {
+        builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(pki.roots()).boxed())
+    } else {
+        builder.with_no_client_auth()
+    };
+    builder.with_single_cert(certs, key).unwrap()
+}
+
+async fn read_frame(stream: &mut (impl AsyncRead + Unpin)) -> Option<Vec<u8>> {
+    let len = stream.read_i32().await.ok()?;
+    let mut buf = vec![0; len as usize];
+    stream.read_exact(&mut buf).await.ok()?;
+    Some(buf)
+}
+
+/// Serves ZooKeeper session with no nodes.
+async fn serve_stub(mut stream: impl AsyncRead + AsyncWrite + Unpin) {
+    let request = match read_frame(&mut stream).await {
+        None => return,
+        Some(request) => request,
+    };
+    let timeout = i32::from_be_bytes(request[12..16].try_into().unwrap());
+    let mut response = vec![];
+    response.extend_from_slice(&0i32.to_be_bytes());
+    response.extend_from_slice(&timeout.to_be_bytes());
+    response.extend_from_slice(&1i64.to_be_bytes());
+    response.extend_from_slice(&16i32.to_be_bytes());
+    response.extend_from_slice(&[0; 16]);
+    response.push(0);
+    stream.write_i32(response.len() as i32).await.unwrap();
+    stream.write_all(&response).await.unwrap();
+    while let Some(request) = read_frame(&mut stream).await {
+        let xid = i32::from_be_bytes(request[0..4].try_into().unwrap());
+        let op_code = i32::from_be_bytes(request[4..8].try_into().unwrap());
+        // Not found for all requests except ping and close.
+        let err = if op_code == 11 || op_code == -11 { 0 } else { -101 };
+        stream.write_i32(16).await.unwrap();
+        stream.write_i32(xid).await.unwrap();
+        stream.write_i64(1).await.unwrap();
+        stream.write_i32(err).await.unwrap();
+        stream.flush().await.unwrap();
+        if op_code == -11 {
+            break;
+        }
+    }
+    stream.shutdown().await.ok();
+}
+
+async fn start_server(config: ServerConfig) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                if let Ok(stream) = acceptor.accept(stream).await {
+                    serve_stub(stream).await;
+                }
+            });
+        }
+    });
+    port
+}
+
+async fn connect(cluster: &str, options: zk::TlsOptions) -> Result<zk::Client, zk::Error> {
+    zk::Client::builder().with_tls(options).connect(cluster).await
+}
+
+#[tokio::test]
+async fn test_tls() {
+    let pki = Pki::new();
+    let port = start_server(server_config(&pki, &["localhost"], false)).await;
+    let options = zk::TlsOptions::default().with_pem_ca_certs(&pki.ca_pem()).unwrap();
+
+    let client = connect(&format!("localhost:{}", port), options).await.unwrap();
+    assert_eq!(client.get_data("/a").await.unwrap_err(), zk::Error::NoNode);
+    assert_eq!(client.check_stat("/a").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_tls_client_auth() {
+    let pki = Pki::new();
+    let port = start_server(server_config(&pki, &["localhost"], true)).await;
+    let cluster = format!("localhost:{}", port);
+    let options = zk::TlsOptions::default().with_pem_ca_certs(&pki.ca_pem()).unwrap();
+
+    let (cert, key) = pki.issue_pem(&["client"]);
+    let client = connect(&cluster, options.clone().with_pem_identity(&cert, &key).unwrap()).await.unwrap();
+    assert_eq!(client.get_data("/a").await.unwrap_err(), zk::Error::NoNode);
+
+    // Server rejects client without certificate after handshake in tls 1.3.
+    match connect(&cluster, options).await {
+        Err(_) => {},
+        Ok(client) => assert_matches!(client.get_data("/a").await.unwrap_err(), zk::Error::ConnectionLoss),
+    }
+}
+
+#[tokio::test]
+async fn test_tls_hostname_verification() {
+    let pki = Pki::new();
+    let port = start_server(server_config(&pki, &["localhost"], false)).await;
+    let cluster = format!("127.0.0.1:{}", port);
+    let options = zk::TlsOptions::default().with_pem_ca_certs(&pki.ca_pem()).unwrap();
+
+    assert_eq!(connect(&cluster, options.clone()).await.unwrap_err(), zk::Error::NoHosts);
+
+    let client = connect(&cluster, options.with_hostname_verification(false)).await.unwrap();
+    assert_eq!(client.get_data("/a").await.unwrap_err(), zk::Error::NoNode);
+}
+
+#[tokio::test]
+async fn test_tls_untrusted() {
+    let pki = Pki::new();
+    let port = start_server(server_config(&pki, &["localhost"], false)).await;
+    let cluster = format!("localhost:{}", port);
+
+    let options = zk::TlsOptions::default().with_pem_ca_certs(&Pki::new().ca_pem()).unwrap();
+    assert_eq!(connect(&cluster, options.with_hostname_verification(false)).await.unwrap_err(), zk::Error::NoHosts);
+}
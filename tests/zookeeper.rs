@@ -379,6 +379,141 @@ async fn test_lock_curator_filter() {
     let _lock = client.lock(lock_prefix, b"", options).await.unwrap();
 }
 
+#[tokio::test]
+async fn test_lock_curator_read_write() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::LockOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+
+    // Readers share lock.
+    let read_prefix = zk::LockPrefix::new_curator_read("/locks/rw").unwrap();
+    let reader1 = client.lock(read_prefix, b"", options.clone()).await.unwrap();
+    assert!(reader1.lock_path().contains("-__READ__"), "{}", reader1.lock_path());
+    let read_prefix = zk::LockPrefix::new_curator_read("/locks/rw").unwrap();
+    let reader2 = client.lock(read_prefix, b"", options.clone()).await.unwrap();
+
+    // Writer waits for earlier readers.
+    let write_prefix = zk::LockPrefix::new_curator_write("/locks/rw").unwrap();
+    let mut writing = Box::pin(client.lock(write_prefix, b"", options.clone()));
+    select! {
+        _ = &mut writing => panic!("expect writer to wait for readers"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    drop(reader1);
+    select! {
+        _ = &mut writing => panic!("expect writer to wait for readers"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    drop(reader2);
+    let writer = writing.await.unwrap();
+    assert!(writer.lock_path().contains("-__WRIT__"), "{}", writer.lock_path());
+
+    // Reader waits for earlier writer.
+    let read_prefix = zk::LockPrefix::new_curator_read("/locks/rw").unwrap();
+    let mut reading = Box::pin(client.lock(read_prefix, b"", options.clone()));
+    select! {
+        _ = &mut reading => panic!("expect reader to wait for writer"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    drop(writer);
+    let _reader = reading.await.unwrap();
+
+    // Other curator locks in same directory are not contenders.
+    let lock_prefix = zk::LockPrefix::new_curator("/locks/rw", "lock-").unwrap();
+    let _lock = client.lock(lock_prefix, b"", options).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_try_lock() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::LockOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+
+    let prefix = zk::LockPrefix::new_curator("/locks/try", "lock-").unwrap();
+    let lock = client.try_lock(prefix, b"", options.clone()).await.unwrap().unwrap();
+
+    // Contender path is deleted before return.
+    let prefix = zk::LockPrefix::new_curator("/locks/try", "lock-").unwrap();
+    assert!(client.try_lock(prefix, b"", options.clone()).await.unwrap().is_none());
+    assert_eq!(client.list_children("/locks/try").await.unwrap().len(), 1);
+
+    let prefix = zk::LockPrefix::new_curator("/locks/try", "lock-").unwrap();
+    let timeout = Duration::from_millis(100);
+    assert!(client.lock_with_timeout(prefix, b"", options.clone(), timeout).await.unwrap().is_none());
+    assert_eq!(client.list_children("/locks/try").await.unwrap().len(), 1);
+
+    // Lock is acquired in timeout after released.
+    let prefix = zk::LockPrefix::new_curator("/locks/try", "lock-").unwrap();
+    let timeout = Duration::from_secs(10);
+    let releasing = async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(lock);
+    };
+    let (_, locked) = tokio::join!(releasing, client.lock_with_timeout(prefix, b"", options, timeout));
+    assert!(locked.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_lock_release_fail_fast() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client =
+        zk::Client::builder().with_max_outstanding_requests(1).with_fail_fast(true).connect(&cluster).await.unwrap();
+    let options = zk::LockOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+    let prefix = zk::LockPrefix::new_curator("/locks/fail-fast", "lock-").unwrap();
+    let lock = client.lock(prefix, b"", options).await.unwrap();
+
+    let observer = zk::Client::connect(&cluster).await.unwrap();
+    let (_, _, watcher) = observer.get_and_watch_children("/locks/fail-fast").await.unwrap();
+
+    // Lock path is deleted after outstanding request completed.
+    let pending = client.check_stat("/a");
+    drop(lock);
+    assert_eq!(pending.await.unwrap(), None);
+    assert_eq!(watcher.changed().await.event_type, zk::EventType::NodeChildrenChanged);
+    assert_eq!(observer.list_children("/locks/fail-fast").await.unwrap(), Vec::<String>::new());
+}
+
+#[tokio::test]
+async fn test_lock_fencing_token() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::LockOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+
+    let prefix = zk::LockPrefix::new_curator("/locks/fencing", "lock-").unwrap();
+    let lock = client.lock(prefix, b"", options.clone()).await.unwrap();
+    let (_, stat) = client.get_data(lock.lock_path()).await.unwrap();
+    assert_eq!(lock.fencing_token(), stat.czxid);
+    lock.check_held().await.unwrap();
+    let token = lock.fencing_token();
+    drop(lock);
+
+    // Successive holder gets larger token.
+    let prefix = zk::LockPrefix::new_curator("/locks/fencing", "lock-").unwrap();
+    let lock = client.lock(prefix, b"", options).await.unwrap().into_owned();
+    assert_that!(lock.fencing_token()).is_greater_than(token);
+    lock.check_held().await.unwrap();
+
+    // Lock is lost after lock path deleted.
+    client.delete(lock.lock_path(), None).await.unwrap();
+    assert_eq!(lock.check_held().await.unwrap_err(), zk::Error::RuntimeInconsistent);
+}
+
 async fn test_lock_with_path(
     cluster: &str,
     chroot: &str,
@@ -405,6 +540,36 @@ async fn test_lock_with_path(
     assert_that!(contender2.await.unwrap()).is_equal_to((b"a1".to_vec(), stat));
 }
 
+#[tokio::test]
+async fn test_lock_cancelled_in_creation() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    client.create("/locks", Default::default(), PERSISTENT_OPEN).await.unwrap();
+
+    // Poll once to send creation request and then cancel locking.
+    let prefix = zk::LockPrefix::new_curator("/locks", "lock-").unwrap();
+    let mut locking = Box::pin(client.lock(prefix, b"", zk::Acls::anyone_all()));
+    select! {
+        biased;
+        _ = &mut locking => panic!("expect lock creation in flight"),
+        _ = std::future::ready(()) => {},
+    }
+    drop(locking);
+
+    // Leftover lock node is deleted in background.
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !client.list_children("/locks").await.unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
 #[tokio::test]
 async fn test_no_node() {
     let docker = DockerCli::default();
@@ -1206,6 +1371,609 @@ async fn test_client_detach() {
     zk::Client::builder().with_session(id, password).connect(&cluster).await.unwrap();
 }
 
+#[tokio::test]
+async fn test_persistent_ephemeral_node() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::CreateMode::Ephemeral.with_acls(zk::Acls::anyone_all());
+    let mut node = zk::PersistentEphemeralNode::new(&client, "/node", b"data1", &options).await.unwrap();
+    assert_eq!(node.path(), Some("/node".to_string()));
+    assert_eq!(client.get_data("/node").await.unwrap().0, b"data1".to_vec());
+
+    // Re-created after deletion.
+    let (_, watcher) = client.check_and_watch_stat("/node").await.unwrap();
+    client.delete("/node", None).await.unwrap();
+    assert_eq!(watcher.changed().await.event_type, zk::EventType::NodeDeleted);
+    while node.changed().await.is_none() {}
+    assert_eq!(node.path(), Some("/node".to_string()));
+
+    // Data is updated in background.
+    let (data, _, watcher) = client.get_and_watch_data("/node").await.unwrap();
+    assert_eq!(data, b"data1".to_vec());
+    node.set_data(b"data2".to_vec());
+    assert_eq!(watcher.changed().await.event_type, zk::EventType::NodeDataChanged);
+    assert_eq!(client.get_data("/node").await.unwrap().0, b"data2".to_vec());
+
+    node.close().await.unwrap();
+    assert_eq!(client.check_stat("/node").await.unwrap(), None);
+
+    // Sequential node gets new path after re-creation.
+    let options = zk::CreateMode::EphemeralSequential.with_acls(zk::Acls::anyone_all());
+    let mut node = zk::PersistentEphemeralNode::new(&client, "/seq-", b"", &options).await.unwrap();
+    let path1 = node.path().unwrap();
+    assert!(path1.starts_with("/_c_") && path1.contains("-seq-"), "{}", path1);
+    client.delete(&path1, None).await.unwrap();
+    let path2 = loop {
+        if let Some(path) = node.changed().await {
+            break path;
+        }
+    };
+    assert_eq!(path2[..path2.len() - 10], path1[..path1.len() - 10]);
+    assert_ne!(path1, path2);
+
+    // Node is deleted after dropped.
+    drop(node);
+    let (_, watcher) = client.check_and_watch_stat(&path2).await.unwrap();
+    assert_eq!(watcher.changed().await.event_type, zk::EventType::NodeDeleted);
+
+    // Node in re-creation is deleted after closed.
+    let mut node = zk::PersistentEphemeralNode::new(&client, "/seq-", b"", &options).await.unwrap();
+    client.delete(&node.path().unwrap(), None).await.unwrap();
+    while node.changed().await.is_some() {}
+    node.close().await.unwrap();
+    assert_eq!(client.list_children("/").await.unwrap().iter().filter(|child| child.starts_with("_c_")).count(), 0);
+}
+
+#[tokio::test]
+async fn test_persistent_ephemeral_node_session_recreation() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::builder().with_session_recreation(true).connect(&cluster).await.unwrap();
+    let mut state_watcher = client.state_watcher();
+    let options = zk::CreateMode::Ephemeral.with_acls(zk::Acls::anyone_all());
+    let mut node = zk::PersistentEphemeralNode::new(&client, "/node", b"data", &options).await.unwrap();
+    let session_id = client.session_id();
+
+    // Expires session by closing it from another client.
+    let stealer = zk::Client::builder()
+        .with_session(session_id, client.session_password())
+        .connect(&cluster)
+        .await
+        .unwrap();
+    drop(stealer);
+    while state_watcher.changed().await != zk::SessionState::Reestablished {}
+    assert_ne!(client.session_id(), session_id);
+
+    loop {
+        if let Some(path) = node.changed().await {
+            assert_eq!(path, "/node");
+            break;
+        }
+    }
+    let (data, stat) = client.get_data("/node").await.unwrap();
+    assert_eq!(data, b"data".to_vec());
+    assert_eq!(stat.ephemeral_owner, client.session_id().0);
+}
+
+#[tokio::test]
+async fn test_leader_latch() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::LockOptions::new(zk::Acls::anyone_all())
+        .with_ancestor_options(zk::CreateMode::Persistent.with_acls(zk::Acls::anyone_all()))
+        .unwrap();
+
+    let mut latch1 = zk::LeaderLatch::new(&client, "/latch", "leader1", options.clone()).unwrap();
+    assert_eq!(latch1.id(), "leader1");
+    assert_eq!(latch1.changed().await, zk::LeaderEvent::Gained);
+    assert!(latch1.is_leader());
+
+    let mut latch2 = zk::LeaderLatch::new(&client, "/latch", "leader2", options).unwrap();
+    while latch2.participants().await.unwrap().len() != 2 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!latch2.is_leader());
+    assert_eq!(latch2.participants().await.unwrap(), vec!["leader1".to_string(), "leader2".to_string()]);
+    assert_eq!(latch2.leader_id().await.unwrap(), Some("leader1".to_string()));
+
+    // Leadership is handed over after leader closed.
+    latch1.close().await.unwrap();
+    assert_eq!(latch2.changed().await, zk::LeaderEvent::Gained);
+    assert!(latch2.is_leader());
+    assert_eq!(latch2.leader_id().await.unwrap(), Some("leader2".to_string()));
+
+    // Leadership is lost after lock path deleted externally, and regained by recontention.
+    let children = client.list_children("/latch").await.unwrap();
+    assert_eq!(children.len(), 1);
+    client.delete(&format!("/latch/{}", children[0]), None).await.unwrap();
+    assert_eq!(latch2.changed().await, zk::LeaderEvent::Lost);
+    assert_eq!(latch2.changed().await, zk::LeaderEvent::Gained);
+
+    drop(latch2);
+    let latch = zk::LeaderLatch::new(&client, "/latch", "leader3", zk::Acls::anyone_all()).unwrap();
+    loop {
+        let participants = latch.participants().await.unwrap();
+        if participants == vec!["leader3".to_string()] {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_inter_process_semaphore() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::LockOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+    assert_eq!(
+        zk::InterProcessSemaphore::new(&client, "/semaphore", 0, options.clone()).unwrap_err(),
+        zk::Error::BadArguments(&"semaphore must have at least one lease")
+    );
+    let semaphore = zk::InterProcessSemaphore::new(&client, "/semaphore", 2, options).unwrap();
+    assert_eq!(semaphore.max_leases(), 2);
+
+    let lease1 = semaphore.acquire(b"lease1").await.unwrap();
+    assert!(lease1.path().starts_with("/semaphore/leases/_c_"), "{}", lease1.path());
+    assert_eq!(client.get_data(lease1.path()).await.unwrap().0, b"lease1".to_vec());
+    let lease2 = semaphore.acquire(b"lease2").await.unwrap();
+
+    // No more leases.
+    let mut acquiring = Box::pin(semaphore.acquire(b"lease3"));
+    select! {
+        _ = &mut acquiring => panic!("expect no available lease"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+
+    // Released lease is available again.
+    lease1.release().await.unwrap();
+    let lease3 = acquiring.await.unwrap();
+    assert_eq!(client.list_children("/semaphore/leases").await.unwrap().len(), 2);
+
+    // Lease is deleted after dropped.
+    let (_, watcher) = client.check_and_watch_stat(lease2.path()).await.unwrap();
+    drop(lease2);
+    assert_eq!(watcher.changed().await.event_type, zk::EventType::NodeDeleted);
+    drop(lease3);
+}
+
+#[tokio::test]
+async fn test_inter_process_mutex() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::LockOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+    let mut mutex1 = zk::InterProcessMutex::new(&client, "/locks/mutex", options.clone()).unwrap();
+    let mut mutex2 = zk::InterProcessMutex::new(&client, "/locks/mutex", options).unwrap();
+    assert!(!mutex1.is_held());
+
+    // Reentrance shares one lock path.
+    let guard1 = mutex1.acquire().await.unwrap();
+    let guard2 = mutex1.acquire().await.unwrap();
+    let guard3 = guard2.clone();
+    assert_eq!(mutex1.hold_count(), 3);
+    assert_eq!(guard1.lock_path(), guard2.lock_path());
+    assert_eq!(client.list_children("/locks/mutex").await.unwrap().len(), 1);
+
+    // Other holder waits until all guards dropped.
+    let mut acquiring = Box::pin(mutex2.acquire());
+    select! {
+        _ = &mut acquiring => panic!("expect lock held by other holder"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    drop(guard1);
+    drop(guard2);
+    assert_eq!(mutex1.hold_count(), 1);
+    select! {
+        _ = &mut acquiring => panic!("expect lock held by other holder"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    let lock_path = guard3.lock_path().to_string();
+    drop(guard3);
+    assert!(!mutex1.is_held());
+    let guard = acquiring.await.unwrap();
+    assert!(mutex2.is_held());
+    assert_ne!(guard.lock_path(), lock_path);
+    assert_eq!(client.check_stat(&lock_path).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_barrier() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let barrier = zk::Barrier::new(&client, "/barrier").unwrap();
+
+    // No barrier, no wait.
+    barrier.wait().await.unwrap();
+
+    barrier.set(PERSISTENT_OPEN).await.unwrap();
+    barrier.set(PERSISTENT_OPEN).await.unwrap();
+    let mut waiting = Box::pin(barrier.wait());
+    select! {
+        _ = &mut waiting => panic!("expect barrier to block"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    barrier.remove().await.unwrap();
+    waiting.await.unwrap();
+    barrier.remove().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_double_barrier() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::RecipeOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+    let barrier = zk::DoubleBarrier::new(&client, "/barriers/double", 3, options).unwrap();
+
+    // Cancelled participant does not count.
+    select! {
+        _ = barrier.enter() => panic!("expect participant to wait for others"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+
+    let mut entering = Box::pin(async { tokio::try_join!(barrier.enter(), barrier.enter()) });
+    select! {
+        _ = &mut entering => panic!("expect participants to wait for others"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    let (member3, (member1, member2)) = tokio::try_join!(barrier.enter(), entering).unwrap();
+    assert_eq!(client.list_children("/barriers/double").await.unwrap().len(), 4);
+
+    // Participants leave after all left.
+    let mut leaving = Box::pin(async { tokio::try_join!(member1.leave(), member2.leave()) });
+    select! {
+        _ = &mut leaving => panic!("expect participants to wait for others to leave"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    tokio::try_join!(member3.leave(), leaving).unwrap();
+    assert_that!(client.list_children("/barriers/double").await.unwrap()).is_empty();
+}
+
+#[tokio::test]
+async fn test_distributed_queue() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::RecipeOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+    let queue = zk::DistributedQueue::new(&client, "/queues/fifo", options).unwrap();
+
+    assert_eq!(queue.peek().await.unwrap(), None);
+    assert_eq!(queue.poll().await.unwrap(), None);
+
+    // Taker waits for item.
+    let mut taking = Box::pin(queue.take());
+    select! {
+        _ = &mut taking => panic!("expect taker to wait for item"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {},
+    }
+    let path = queue.offer(b"item1").await.unwrap();
+    assert!(path.starts_with("/queues/fifo/qn-"), "{}", path);
+    assert_eq!(taking.await.unwrap(), b"item1".to_vec());
+
+    queue.offer(b"item2").await.unwrap();
+    queue.offer(b"item3").await.unwrap();
+    assert_eq!(queue.peek().await.unwrap(), Some(b"item2".to_vec()));
+    assert_eq!(queue.take().await.unwrap(), b"item2".to_vec());
+    assert_eq!(queue.poll().await.unwrap(), Some(b"item3".to_vec()));
+    assert_eq!(queue.poll().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_distributed_priority_queue() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::RecipeOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+    let queue = zk::DistributedPriorityQueue::new(&client, "/queues/priority", options).unwrap();
+
+    queue.offer(b"low", 10).await.unwrap();
+    queue.offer(b"high1", -5).await.unwrap();
+    queue.offer(b"normal", 0).await.unwrap();
+    queue.offer(b"high2", -5).await.unwrap();
+
+    assert_eq!(queue.peek().await.unwrap(), Some(b"high1".to_vec()));
+    assert_eq!(queue.take().await.unwrap(), b"high1".to_vec());
+    assert_eq!(queue.take().await.unwrap(), b"high2".to_vec());
+    assert_eq!(queue.take().await.unwrap(), b"normal".to_vec());
+    assert_eq!(queue.take().await.unwrap(), b"low".to_vec());
+    assert_eq!(queue.poll().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_distributed_atomic_long() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::RecipeOptions::new(zk::Acls::anyone_all()).with_ancestor_options(CONTAINER_OPEN.clone()).unwrap();
+    let counter = zk::DistributedAtomicLong::new(&client, "/counters/jobs", options).unwrap();
+
+    // Absent counter is zero.
+    assert_eq!(counter.get().await.unwrap(), 0);
+    assert_eq!(counter.increment().await.unwrap(), zk::AtomicValue { succeeded: true, pre_value: 0, post_value: 1 });
+    assert_eq!(client.get_data("/counters/jobs").await.unwrap().0, 1i64.to_be_bytes().to_vec());
+    assert_eq!(counter.add(10).await.unwrap(), zk::AtomicValue { succeeded: true, pre_value: 1, post_value: 11 });
+    assert_eq!(counter.decrement().await.unwrap(), zk::AtomicValue { succeeded: true, pre_value: 11, post_value: 10 });
+
+    assert_eq!(counter.compare_and_set(5, 20).await.unwrap(), zk::AtomicValue {
+        succeeded: false,
+        pre_value: 10,
+        post_value: 10
+    });
+    assert_eq!(counter.compare_and_set(10, 20).await.unwrap(), zk::AtomicValue {
+        succeeded: true,
+        pre_value: 10,
+        post_value: 20
+    });
+    assert_eq!(counter.try_set(-3).await.unwrap(), zk::AtomicValue { succeeded: true, pre_value: 20, post_value: -3 });
+
+    // Concurrent increments are not lost.
+    let counter = counter.with_attempts(1).unwrap().with_promoted_lock("/counters/locks").unwrap();
+    let increments: Vec<_> = (0..10)
+        .map(|_| {
+            let counter = counter.clone();
+            tokio::spawn(async move { counter.increment().await.unwrap() })
+        })
+        .collect();
+    let mut succeeded = 0;
+    for increment in increments {
+        if increment.await.unwrap().succeeded {
+            succeeded += 1;
+        }
+    }
+    assert_eq!(counter.get().await.unwrap(), -3 + succeeded);
+}
+
+#[tokio::test]
+async fn test_node_cache() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let mut cache = zk::NodeCache::new(&client, "/cached").await.unwrap();
+    assert_eq!(cache.path(), "/cached");
+    assert_eq!(cache.current(), None);
+
+    // Creation.
+    let (stat, _) = client.create("/cached", b"data1", PERSISTENT_OPEN).await.unwrap();
+    assert_eq!(cache.changed().await, Some((b"data1".to_vec(), stat)));
+    assert_eq!(cache.current(), Some((b"data1".to_vec(), stat)));
+
+    // Data change.
+    let stat = client.set_data("/cached", b"data2", None).await.unwrap();
+    assert_eq!(cache.changed().await, Some((b"data2".to_vec(), stat)));
+
+    // Deletion.
+    client.delete("/cached", None).await.unwrap();
+    assert_eq!(cache.changed().await, None);
+    assert_eq!(cache.current(), None);
+}
+
+#[tokio::test]
+async fn test_tree_cache() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let (root_stat, _) = client.create("/tree", b"root", PERSISTENT_OPEN).await.unwrap();
+    let (a_stat, _) = client.create("/tree/a", b"a", PERSISTENT_OPEN).await.unwrap();
+
+    let mut cache = zk::TreeCache::new(&client, "/tree").await.unwrap();
+    assert_eq!(cache.root(), "/tree");
+    assert_eq!(cache.get("/tree/a"), Some((b"a".to_vec(), a_stat)));
+
+    // Bootstrap.
+    assert_eq!(cache.changed().await, zk::TreeCacheEvent::Added {
+        path: "/tree".to_string(),
+        data: b"root".to_vec(),
+        stat: root_stat
+    });
+    assert_eq!(cache.changed().await, zk::TreeCacheEvent::Added {
+        path: "/tree/a".to_string(),
+        data: b"a".to_vec(),
+        stat: a_stat
+    });
+    assert_eq!(cache.changed().await, zk::TreeCacheEvent::Initialized);
+    assert_eq!(cache.paths(), vec!["/tree".to_string(), "/tree/a".to_string()]);
+
+    // Creation of descendant.
+    let (b_stat, _) = client.create("/tree/a/b", b"b", PERSISTENT_OPEN).await.unwrap();
+    assert_eq!(cache.changed().await, zk::TreeCacheEvent::Added {
+        path: "/tree/a/b".to_string(),
+        data: b"b".to_vec(),
+        stat: b_stat
+    });
+    assert_eq!(cache.get("/tree/a/b"), Some((b"b".to_vec(), b_stat)));
+
+    // Data change.
+    let stat = client.set_data("/tree/a", b"a2", None).await.unwrap();
+    assert_eq!(cache.changed().await, zk::TreeCacheEvent::Updated {
+        path: "/tree/a".to_string(),
+        data: b"a2".to_vec(),
+        stat
+    });
+
+    // Deletion.
+    client.delete("/tree/a/b", None).await.unwrap();
+    assert_eq!(cache.changed().await, zk::TreeCacheEvent::Removed {
+        path: "/tree/a/b".to_string(),
+        data: b"b".to_vec(),
+        stat: b_stat
+    });
+    assert_eq!(cache.get("/tree/a/b"), None);
+    assert_eq!(cache.snapshot().len(), 2);
+}
+
+#[tokio::test]
+async fn test_path_children_cache() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    client.create("/group", Default::default(), PERSISTENT_OPEN).await.unwrap();
+    let (a_stat, _) = client.create("/group/a", b"a", PERSISTENT_OPEN).await.unwrap();
+
+    let mut cache = zk::PathChildrenCache::new(&client, "/group", true).await.unwrap();
+    let names_client = zk::Client::connect(&cluster).await.unwrap();
+    let mut names_cache = zk::PathChildrenCache::new(&names_client, "/group", false).await.unwrap();
+    assert_eq!(cache.path(), "/group");
+
+    // Bootstrap.
+    let a = zk::ChildData { path: "/group/a".to_string(), data: Some((b"a".to_vec(), a_stat)) };
+    assert_eq!(cache.changed().await, zk::PathChildrenCacheEvent::Added(a.clone()));
+    assert_eq!(cache.changed().await, zk::PathChildrenCacheEvent::Initialized);
+    assert_eq!(cache.children(), vec![a]);
+    let a = zk::ChildData { path: "/group/a".to_string(), data: None };
+    assert_eq!(names_cache.changed().await, zk::PathChildrenCacheEvent::Added(a.clone()));
+    assert_eq!(names_cache.changed().await, zk::PathChildrenCacheEvent::Initialized);
+    assert_eq!(names_cache.children(), vec![a]);
+
+    // Creation of child.
+    let (b_stat, _) = client.create("/group/b", b"b", PERSISTENT_OPEN).await.unwrap();
+    let b = zk::ChildData { path: "/group/b".to_string(), data: Some((b"b".to_vec(), b_stat)) };
+    assert_eq!(cache.changed().await, zk::PathChildrenCacheEvent::Added(b.clone()));
+    assert_eq!(cache.get("/group/b"), Some(b));
+    let b = zk::ChildData { path: "/group/b".to_string(), data: None };
+    assert_eq!(names_cache.changed().await, zk::PathChildrenCacheEvent::Added(b.clone()));
+
+    // Grandchildren are not cached.
+    client.create("/group/b/c", Default::default(), PERSISTENT_OPEN).await.unwrap();
+    assert_eq!(cache.get("/group/b/c"), None);
+
+    // Data change.
+    let stat = client.set_data("/group/a", b"a2", None).await.unwrap();
+    let a = zk::ChildData { path: "/group/a".to_string(), data: Some((b"a2".to_vec(), stat)) };
+    assert_eq!(cache.changed().await, zk::PathChildrenCacheEvent::Updated(a.clone()));
+
+    // Deletion.
+    client.delete("/group/a", None).await.unwrap();
+    assert_eq!(cache.changed().await, zk::PathChildrenCacheEvent::Removed(a));
+    let a = zk::ChildData { path: "/group/a".to_string(), data: None };
+    assert_eq!(names_cache.changed().await, zk::PathChildrenCacheEvent::Removed(a));
+    assert_eq!(cache.children().len(), 1);
+    assert_eq!(names_cache.children(), vec![b]);
+}
+
+#[cfg(feature = "discovery")]
+#[tokio::test]
+async fn test_service_discovery() {
+    let docker = DockerCli::default();
+    let zookeeper = docker.run(zookeeper_image());
+    let zk_port = zookeeper.get_host_port(2181);
+    let cluster = format!("127.0.0.1:{}", zk_port);
+
+    let client = zk::Client::connect(&cluster).await.unwrap();
+    let options = zk::RecipeOptions::new(zk::Acls::anyone_all())
+        .with_ancestor_options(zk::CreateMode::Persistent.with_acls(zk::Acls::anyone_all()))
+        .unwrap();
+    let discovery = zk::ServiceDiscovery::new(&client, "/services", options).unwrap();
+    assert_eq!(discovery.query_names().await.unwrap(), Vec::<String>::new());
+
+    // Register.
+    let mut instance1 = zk::ServiceInstance::new("echo");
+    instance1.address = Some("10.0.0.1".to_string());
+    instance1.port = Some(8080);
+    discovery.register(&instance1).await.unwrap();
+    let (_, stat) = client.get_data(&format!("/services/echo/{}", instance1.id)).await.unwrap();
+    assert_eq!(stat.ephemeral_owner, client.session_id().0);
+    assert_eq!(discovery.query_names().await.unwrap(), vec!["echo".to_string()]);
+    assert_eq!(discovery.query_instance("echo", &instance1.id).await.unwrap(), Some(instance1.clone()));
+
+    let mut instance2 = zk::ServiceInstance::new("echo");
+    instance2.address = Some("10.0.0.2".to_string());
+    discovery.register(&instance2).await.unwrap();
+    let mut instances = discovery.query_instances("echo").await.unwrap();
+    instances.sort_by(|a, b| a.address.cmp(&b.address));
+    assert_eq!(instances, vec![instance1.clone(), instance2.clone()]);
+
+    // Round-robin provider.
+    let provider = discovery.provider("echo", zk::ProviderStrategy::RoundRobin).await.unwrap();
+    assert_eq!(provider.instances().len(), 2);
+    let mut selected = vec![provider.instance().unwrap(), provider.instance().unwrap()];
+    selected.sort_by(|a, b| a.address.cmp(&b.address));
+    assert_eq!(selected, vec![instance1.clone(), instance2.clone()]);
+
+    // Update.
+    instance2.enabled = false;
+    discovery.register(&instance2).await.unwrap();
+    // Update is written in background.
+    while discovery.query_instance("echo", &instance2.id).await.unwrap() != Some(instance2.clone()) {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let random = discovery.provider("echo", zk::ProviderStrategy::Random).await.unwrap();
+    assert_eq!(random.instance(), Some(instance1.clone()));
+
+    // Node of other session is replaced.
+    let other_client = zk::Client::connect(&cluster).await.unwrap();
+    let instance3 = zk::ServiceInstance::new("echo");
+    let path3 = format!("/services/echo/{}", instance3.id);
+    let options = zk::CreateMode::Ephemeral.with_acls(zk::Acls::anyone_all());
+    other_client.create(&path3, Default::default(), &options).await.unwrap();
+    discovery.register(&instance3).await.unwrap();
+    let (_, stat) = client.get_data(&path3).await.unwrap();
+    assert_eq!(stat.ephemeral_owner, client.session_id().0);
+    discovery.unregister("echo", &instance3.id).await.unwrap();
+
+    // Unregister.
+    discovery.unregister("echo", &instance1.id).await.unwrap();
+    assert_eq!(discovery.query_instance("echo", &instance1.id).await.unwrap(), None);
+    assert_eq!(discovery.query_instances("echo").await.unwrap(), vec![instance2.clone()]);
+
+    // Instances are unregistered after registry dropped.
+    drop(discovery);
+    let discovery = zk::ServiceDiscovery::new(&client, "/services", zk::Acls::anyone_all()).unwrap();
+    while !discovery.query_instances("echo").await.unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // Provider fails if instances are not readable.
+    client.auth("digest".to_string(), b"bob:xyz".to_vec()).await.unwrap();
+    let options = zk::CreateMode::Persistent.with_acls(zk::Acls::creator_all());
+    client.create("/services/secret", Default::default(), &options).await.unwrap();
+    let no_auth_client = zk::Client::connect(&cluster).await.unwrap();
+    let discovery = zk::ServiceDiscovery::new(&no_auth_client, "/services", zk::Acls::anyone_all()).unwrap();
+    assert_eq!(discovery.provider("secret", zk::ProviderStrategy::Random).await.unwrap_err(), zk::Error::NoAuth);
+}
+
 #[allow(dead_code)]
 fn zookeeper_quorum_image(server_id: u8, dir: &TempDir, servers: &[&str]) -> RunnableImage<GenericImage> {
     let options = r"dataDir=/data
@@ -1270,17 +2038,21 @@ async fn test_update_ensemble() {
     assert_that!(String::from_utf8_lossy(&config_bytes).into_owned()).contains("server.1");
     assert_that!(String::from_utf8_lossy(&config_bytes).into_owned()).does_not_contain("server.2");
     assert_that!(String::from_utf8_lossy(&config_bytes).into_owned()).does_not_contain("server.3");
-    let new_ensemble = zk::EnsembleUpdate::New {
-        ensemble: vec![
-            "server.1=localhost:2001:3001:participant;localhost:4001",
-            "server.2=localhost:2002:3002:participant;localhost:4002",
-            "server.3=localhost:2003:3003:participant;localhost:4003",
-        ]
-        .into_iter(),
-    };
+    let config = zk::QuorumConfig::from_bytes(&config_bytes).unwrap();
+    assert_eq!(config.servers.iter().map(|server| server.id).collect::<Vec<_>>(), vec![1]);
+
+    let ensemble: Vec<zk::ServerSpec> = (1..=3)
+        .map(|i| {
+            zk::ServerSpec::new(i, zk::QuorumAddress::new("localhost", 2000 + i as u16, 3000 + i as u16))
+                .with_client_address("localhost", 4000 + i as u16)
+        })
+        .collect();
+    let new_ensemble = zk::EnsembleUpdate::New { ensemble: &ensemble };
     zoo1_client.auth("digest".to_string(), b"super:test".to_vec()).await.unwrap();
     let (new_config_bytes, _) = zoo1_client.update_ensemble(new_ensemble, Some(config_stat.mzxid)).await.unwrap();
     assert_that!(String::from_utf8_lossy(&new_config_bytes).into_owned()).contains("server.1");
     assert_that!(String::from_utf8_lossy(&new_config_bytes).into_owned()).contains("server.2");
     assert_that!(String::from_utf8_lossy(&new_config_bytes).into_owned()).contains("server.3");
+    let new_config = zk::QuorumConfig::from_bytes(&new_config_bytes).unwrap();
+    assert_eq!(new_config.servers.iter().map(|server| server.id).collect::<Vec<_>>(), vec![1, 2, 3]);
 }